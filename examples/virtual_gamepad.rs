@@ -0,0 +1,52 @@
+// Creates a virtual gamepad through uinput that presses South and sweeps the left stick, to try
+// gamepad input without a controller. Needs write access to /dev/uinput, usually root.
+
+#[cfg(target_os = "linux")]
+fn main() -> std::io::Result<()> {
+    use std::{thread::sleep, time::Duration};
+
+    use evdev::{
+        AbsInfo, AbsoluteAxisCode, AttributeSet, EventType, InputEvent, KeyCode, UinputAbsSetup,
+        uinput::VirtualDevice,
+    };
+
+    let mut buttons = AttributeSet::<KeyCode>::new();
+    for button in [
+        KeyCode::BTN_SOUTH,
+        KeyCode::BTN_EAST,
+        KeyCode::BTN_NORTH,
+        KeyCode::BTN_WEST,
+        KeyCode::BTN_SELECT,
+        KeyCode::BTN_START,
+    ] {
+        buttons.insert(button);
+    }
+    let stick = AbsInfo::new(0, -32768, 32767, 16, 128, 0);
+
+    let mut device = VirtualDevice::builder()?
+        .name("Virtual Gamepad")
+        .with_keys(&buttons)?
+        .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisCode::ABS_X, stick))?
+        .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisCode::ABS_Y, stick))?
+        .build()?;
+    for path in device.enumerate_dev_nodes_blocking()? {
+        println!("Available as {}", path?.display());
+    }
+
+    let mut step: i32 = 0;
+    loop {
+        let pressed = (step % 20 < 10) as i32;
+        let x = ((step as f32 / 10.0).sin() * 32767.0) as i32;
+        device.emit(&[
+            InputEvent::new(EventType::KEY.0, KeyCode::BTN_SOUTH.code(), pressed),
+            InputEvent::new(EventType::ABSOLUTE.0, AbsoluteAxisCode::ABS_X.0, x),
+        ])?;
+        step = step.wrapping_add(1);
+        sleep(Duration::from_millis(100));
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("uinput is only available on Linux");
+}
@@ -1,11 +1,32 @@
 mod util;
-use util::winit::app::App;
+use std::{env, fs::File};
+
+use env_logger::{Env, Target};
+use util::{
+    vulkano::vulkano_utils::VulkanSettings,
+    winit::{app::App, window::WindowSettings},
+};
 use winit::event_loop::{ControlFlow, EventLoop};
 
 fn main() {
+    // Verbosity is set through RUST_LOG, e.g. `RUST_LOG=swapchain=debug,info`
+    let mut logger = env_logger::Builder::from_env(Env::default().default_filter_or("info"));
+    if let Ok(path) = env::var("LOG_FILE") {
+        let file = File::create(&path).expect("failed to create log file");
+        logger.target(Target::Pipe(Box::new(file)));
+    }
+    logger.init();
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Wait);
 
-    let mut app = App::default();
+    let mut app = App::new(
+        VulkanSettings {
+            debug: cfg!(debug_assertions),
+            shader_hot_reload: cfg!(debug_assertions),
+            ..Default::default()
+        },
+        WindowSettings::default(),
+    );
     let _ = event_loop.run_app(&mut app);
 }
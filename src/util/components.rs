@@ -1,3 +1,5 @@
+pub mod material;
 pub mod rectangle;
+pub mod scene;
 pub mod shape;
 pub mod triangle;
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use vulkano::{Validated, VulkanError, device::Device, shader::ShaderModule};
+
+/// Signature of the `load` function generated by `vulkano_shaders::shader!`.
+pub type ShaderLoader = fn(Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>>;
+
+/// A fragment shader drawn in place of the default one for the shapes that reference it.
+///
+/// The shader can read the per-frame globals at set 0 and the shape color at set 1, binding 0
+/// like the default fragment shader, and its own parameters from a uniform block at set 2,
+/// binding 0 made of `params.len()` vec4s. It may also `#include "output.glsl"` from the shader
+/// directory and pass its linear color to `encode_output` to encode it for the swapchain, and
+/// declare the `Node` push constants of the default shaders to apply the opacity of the shape's
+/// node.
+///
+/// One pipeline is built per shader and one set of parameters per distinct `params`, the name
+/// only labels them. To change the parameters of a shape, give it a new material.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub fragment_shader: ShaderLoader,
+    pub params: Vec<[f32; 4]>,
+}
+
+impl Material {
+    pub fn new(name: &str, fragment_shader: ShaderLoader, params: Vec<[f32; 4]>) -> Self {
+        Material {
+            name: name.to_owned(),
+            fragment_shader,
+            params,
+        }
+    }
+}
@@ -1,45 +1,64 @@
 use std::sync::Arc;
 
-use vulkano::{buffer::Subbuffer, descriptor_set::DescriptorSet};
+use vulkano::{
+    buffer::{IndexBuffer, Subbuffer},
+    descriptor_set::DescriptorSet,
+};
 
 use crate::util::vulkano::vulkano_utils::SimpleVertex;
 
+use super::material::Material;
+
 #[derive(Clone, Debug)]
 pub struct Rectangle {
     pub vertices: Vec<SimpleVertex>,
+    pub indices: Vec<u32>,
     pub color: [f32; 4],
+    pub material: Option<Arc<Material>>,
+    /// Shapes on higher layers are drawn over lower ones, in insertion order within a layer.
+    pub layer: i32,
+    /// Clip mask the shape is drawn inside of, see `Vulkan::push_clip`.
+    pub clip: Option<usize>,
+    /// Clip rect the shape is drawn inside of, see `Vulkan::add_clip_rect`.
+    pub clip_rect: Option<usize>,
     pub descriptor_set: Option<Arc<DescriptorSet>>,
     pub vertex_buffer: Option<Subbuffer<[SimpleVertex]>>,
+    pub index_buffer: Option<IndexBuffer>,
 }
 
 impl Rectangle {
     pub fn new(x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) -> Self {
         let vertices = vec![
-            // First triangle (top-left, bottom-left, top-right)
+            // Top-left
             SimpleVertex { position: [x, y] },
+            // Bottom-left
             SimpleVertex {
                 position: [x, y + height],
             },
+            // Top-right
             SimpleVertex {
                 position: [x + width, y],
             },
-            // Second triangle (bottom-left, bottom-right, top-right)
-            SimpleVertex {
-                position: [x, y + height],
-            },
+            // Bottom-right
             SimpleVertex {
                 position: [x + width, y + height],
             },
-            SimpleVertex {
-                position: [x + width, y],
-            },
         ];
+        // First triangle (top-left, bottom-left, top-right)
+        // Second triangle (bottom-left, bottom-right, top-right)
+        let indices = vec![0, 1, 2, 1, 3, 2];
         Rectangle {
             vertices,
+            indices,
             color,
-            // Descriptor set and vertex buffer generated automatically in vulkan initialization
+            material: None,
+            layer: 0,
+            clip: None,
+            clip_rect: None,
+            // Descriptor set, vertex and index buffers generated automatically in vulkan initialization
             descriptor_set: None,
             vertex_buffer: None,
+            index_buffer: None,
         }
     }
 }
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+
+/// Column-major 2D affine matrix, the x axis, the y axis and the translation.
+pub type Affine = [[f32; 2]; 3];
+
+pub const IDENTITY_MATRIX: Affine = [[1.0, 0.0], [0.0, 1.0], [0.0, 0.0]];
+
+/// Identifies a node for as long as it is in the scene, IDs of removed nodes are not reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(u64);
+
+/// Placement of a node relative to its parent, in the same coordinates as the vertices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: [f32; 2],
+    /// In radians, turning the x axis towards the y axis, clockwise on screen as y points down.
+    pub rotation: f32,
+    pub scale: [f32; 2],
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: [0.0, 0.0],
+        rotation: 0.0,
+        scale: [1.0, 1.0],
+    };
+
+    /// Scales, then rotates, then translates.
+    pub fn to_matrix(self) -> Affine {
+        let (sin, cos) = self.rotation.sin_cos();
+        [
+            [cos * self.scale[0], sin * self.scale[0]],
+            [-sin * self.scale[1], cos * self.scale[1]],
+            self.translation,
+        ]
+    }
+    pub fn translate(&mut self, delta: [f32; 2]) {
+        self.translation = [
+            self.translation[0] + delta[0],
+            self.translation[1] + delta[1],
+        ];
+    }
+    /// Scales by `factor` after the transform, keeping `center` in place.
+    pub fn scale_about(&mut self, center: [f32; 2], factor: f32) {
+        self.translation =
+            [0, 1].map(|axis| center[axis] + (self.translation[axis] - center[axis]) * factor);
+        self.scale = self.scale.map(|scale| scale * factor);
+    }
+    /// Rotates by `angle` after the transform, keeping `center` in place.
+    pub fn rotate_about(&mut self, center: [f32; 2], angle: f32) {
+        let (sin, cos) = angle.sin_cos();
+        let [x, y] = [
+            self.translation[0] - center[0],
+            self.translation[1] - center[1],
+        ];
+        self.translation = [center[0] + cos * x - sin * y, center[1] + sin * x + cos * y];
+        self.rotation += angle;
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub transform: Transform,
+    /// Hides the node and all of its descendants.
+    pub visible: bool,
+    /// Multiplies the alpha of the node and all of its descendants.
+    pub opacity: f32,
+    element: Option<usize>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    fn new(parent: Option<NodeId>, element: Option<usize>) -> Self {
+        Node {
+            transform: Transform::IDENTITY,
+            visible: true,
+            opacity: 1.0,
+            element,
+            parent,
+            children: Vec::new(),
+        }
+    }
+    /// Index of the element drawn by the node in `Vulkan`, `None` for groups.
+    pub fn element(&self) -> Option<usize> {
+        self.element
+    }
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+    /// Children in drawing order, later ones are drawn over earlier ones on the same layer.
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// An element to draw, with the transform and opacity of its node combined with its ancestors'.
+#[derive(Clone, Copy, Debug)]
+pub struct RenderItem {
+    pub node: NodeId,
+    pub element: usize,
+    pub transform: Affine,
+    pub opacity: f32,
+}
+
+/// Tree of nodes whose transforms, visibility and opacity apply to all of their descendants.
+#[derive(Clone, Debug)]
+pub struct SceneGraph {
+    nodes: HashMap<NodeId, Node>,
+    root: NodeId,
+    next_id: u64,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        let root = NodeId(0);
+        SceneGraph {
+            nodes: HashMap::from([(root, Node::new(None, None))]),
+            root,
+            next_id: 1,
+        }
+    }
+    /// The group every other node descends from, it can't be removed.
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+    /// Adds a node drawing `element` as the last child of `parent`, or a group if `element` is
+    /// `None`.
+    pub fn add_node(&mut self, parent: NodeId, element: Option<usize>) -> NodeId {
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        self.nodes
+            .get_mut(&parent)
+            .expect("parent node not in the scene")
+            .children
+            .push(id);
+        self.nodes.insert(id, Node::new(Some(parent), element));
+        id
+    }
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(&id)
+    }
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(&id)
+    }
+    /// Removes the node and its descendants, returning the elements they drew. The scene of
+    /// `Vulkan` is changed through `Vulkan::remove_node` instead, which frees those elements.
+    pub fn remove(&mut self, id: NodeId) -> Vec<usize> {
+        if id == self.root {
+            return Vec::new();
+        }
+        let Some(node) = self.nodes.get(&id) else {
+            return Vec::new();
+        };
+        if let Some(parent) = node.parent.and_then(|parent| self.nodes.get_mut(&parent)) {
+            parent.children.retain(|child| *child != id);
+        }
+
+        let mut elements = Vec::new();
+        let mut removed = vec![id];
+        while let Some(id) = removed.pop() {
+            let node = self.nodes.remove(&id).unwrap();
+            elements.extend(node.element);
+            removed.extend(node.children);
+        }
+        elements
+    }
+    /// Points the nodes at the new indices of their elements once the `removed` ones are taken
+    /// out of the element list.
+    pub fn shift_elements(&mut self, removed: &[usize]) {
+        for node in self.nodes.values_mut() {
+            if let Some(element) = &mut node.element {
+                *element -= removed
+                    .iter()
+                    .filter(|removed| **removed < *element)
+                    .count();
+            }
+        }
+    }
+    /// Moves the node to the end of `parent`'s children, keeping its local transform.
+    /// Returns false, changing nothing, if `parent` is the node itself or one of its descendants.
+    pub fn set_parent(&mut self, id: NodeId, parent: NodeId) -> bool {
+        let is_descendant = std::iter::successors(Some(parent), |ancestor| {
+            self.nodes.get(ancestor).and_then(|node| node.parent)
+        })
+        .any(|ancestor| ancestor == id);
+        if id == self.root || is_descendant || !self.nodes.contains_key(&parent) {
+            return false;
+        }
+
+        let old_parent = self.nodes[&id].parent.unwrap();
+        self.nodes
+            .get_mut(&old_parent)
+            .unwrap()
+            .children
+            .retain(|child| *child != id);
+        self.nodes.get_mut(&parent).unwrap().children.push(id);
+        self.nodes.get_mut(&id).unwrap().parent = Some(parent);
+        true
+    }
+    /// Transform of the node combined with its ancestors', `None` if it isn't in the scene.
+    pub fn world_transform(&self, id: NodeId) -> Option<Affine> {
+        let mut transform = self.nodes.get(&id)?.transform.to_matrix();
+        let mut parent = self.nodes[&id].parent;
+        while let Some(id) = parent {
+            let node = &self.nodes[&id];
+            transform = multiply(&node.transform.to_matrix(), &transform);
+            parent = node.parent;
+        }
+        Some(transform)
+    }
+    /// Visible elements in drawing order, parents before their children.
+    pub fn flatten(&self) -> Vec<RenderItem> {
+        let mut items = Vec::new();
+        let mut stack = vec![(self.root, IDENTITY_MATRIX, 1.0)];
+        while let Some((id, parent_transform, parent_opacity)) = stack.pop() {
+            let node = &self.nodes[&id];
+            if !node.visible {
+                continue;
+            }
+            let transform = multiply(&parent_transform, &node.transform.to_matrix());
+            let opacity = parent_opacity * node.opacity;
+            if let Some(element) = node.element {
+                items.push(RenderItem {
+                    node: id,
+                    element,
+                    transform,
+                    opacity,
+                });
+            }
+            // Reversed so that the first child is popped first
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|child| (*child, transform, opacity)),
+            );
+        }
+        items
+    }
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        SceneGraph::new()
+    }
+}
+
+/// Maps points through the transform back to the coordinates it was applied to, `None` if the
+/// transform collapses them, e.g. with a scale of 0.0.
+pub fn invert(transform: &Affine) -> Option<Affine> {
+    let [[a, b], [c, d], [tx, ty]] = *transform;
+    let determinant = a * d - b * c;
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse = [
+        [d / determinant, -b / determinant],
+        [-c / determinant, a / determinant],
+        [0.0, 0.0],
+    ];
+    let [x, y] = apply(&inverse, [tx, ty]);
+    Some([inverse[0], inverse[1], [-x, -y]])
+}
+
+pub fn apply(transform: &Affine, point: [f32; 2]) -> [f32; 2] {
+    [
+        transform[0][0] * point[0] + transform[1][0] * point[1] + transform[2][0],
+        transform[0][1] * point[0] + transform[1][1] * point[1] + transform[2][1],
+    ]
+}
+
+/// Applies `child` first, then `parent`.
+pub fn multiply(parent: &Affine, child: &Affine) -> Affine {
+    let apply = |v: [f32; 2], w: f32| {
+        [
+            parent[0][0] * v[0] + parent[1][0] * v[1] + parent[2][0] * w,
+            parent[0][1] * v[0] + parent[1][1] * v[1] + parent[2][1] * w,
+        ]
+    };
+    [
+        apply(child[0], 0.0),
+        apply(child[1], 0.0),
+        apply(child[2], 1.0),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 2], b: [f32; 2]) {
+        assert!(
+            (a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn apply_scales_rotates_then_translates() {
+        let transform = Transform {
+            translation: [1.0, 2.0],
+            rotation: std::f32::consts::FRAC_PI_2,
+            scale: [2.0, 3.0],
+        };
+        // x is scaled to 2.0 then turned onto the y axis
+        assert_close(apply(&transform.to_matrix(), [1.0, 0.0]), [1.0, 4.0]);
+        // y is scaled to 3.0 then turned onto the negative x axis
+        assert_close(apply(&transform.to_matrix(), [0.0, 1.0]), [-2.0, 2.0]);
+    }
+
+    #[test]
+    fn invert_undoes_apply() {
+        let matrix = Transform {
+            translation: [0.5, -0.25],
+            rotation: 0.7,
+            scale: [2.0, 0.5],
+        }
+        .to_matrix();
+        let inverse = invert(&matrix).unwrap();
+        for point in [[0.0, 0.0], [1.0, -1.0], [-0.3, 0.8]] {
+            assert_close(apply(&inverse, apply(&matrix, point)), point);
+        }
+    }
+
+    #[test]
+    fn invert_collapsed_transform() {
+        let matrix = Transform {
+            scale: [0.0, 1.0],
+            ..Transform::IDENTITY
+        }
+        .to_matrix();
+        assert_eq!(invert(&matrix), None);
+    }
+
+    #[test]
+    fn flatten_combines_ancestors() {
+        let mut scene = SceneGraph::new();
+        let group = scene.add_node(scene.root(), None);
+        let child = scene.add_node(group, Some(0));
+        let group_node = scene.get_mut(group).unwrap();
+        group_node.transform.translation = [1.0, 0.0];
+        group_node.opacity = 0.5;
+        scene.get_mut(child).unwrap().transform.scale = [2.0, 2.0];
+
+        let items = scene.flatten();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].node, child);
+        assert_eq!(items[0].opacity, 0.5);
+        assert_close(apply(&items[0].transform, [1.0, 1.0]), [3.0, 2.0]);
+        assert_eq!(scene.world_transform(child), Some(items[0].transform));
+
+        scene.get_mut(group).unwrap().visible = false;
+        assert!(scene.flatten().is_empty());
+    }
+
+    #[test]
+    fn remove_returns_elements_of_descendants() {
+        let mut scene = SceneGraph::new();
+        let group = scene.add_node(scene.root(), Some(0));
+        scene.add_node(group, Some(1));
+        let kept = scene.add_node(scene.root(), Some(2));
+
+        let mut removed = scene.remove(group);
+        removed.sort();
+        assert_eq!(removed, vec![0, 1]);
+        assert!(scene.get(group).is_none());
+        assert_eq!(scene.get(scene.root()).unwrap().children(), &[kept]);
+
+        scene.shift_elements(&removed);
+        assert_eq!(scene.get(kept).unwrap().element(), Some(0));
+        assert!(scene.remove(scene.root()).is_empty());
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut scene = SceneGraph::new();
+        let parent = scene.add_node(scene.root(), None);
+        let child = scene.add_node(parent, None);
+        assert!(!scene.set_parent(parent, child));
+        assert!(!scene.set_parent(parent, parent));
+        assert!(scene.set_parent(child, scene.root()));
+        assert_eq!(scene.get(child).unwrap().parent(), Some(scene.root()));
+    }
+}
@@ -1,10 +1,13 @@
 use std::sync::Arc;
 
-use vulkano::{buffer::Subbuffer, descriptor_set::DescriptorSet};
+use vulkano::{
+    buffer::{IndexBuffer, Subbuffer},
+    descriptor_set::DescriptorSet,
+};
 
 use crate::util::vulkano::vulkano_utils::SimpleVertex;
 
-use super::{rectangle::Rectangle, triangle::Triangle};
+use super::{material::Material, rectangle::Rectangle, triangle::Triangle};
 
 #[derive(Clone)]
 pub enum Shape {
@@ -25,6 +28,55 @@ impl Shape {
             Shape::Rectangle(rectangle) => rectangle.color,
         }
     }
+    pub fn get_material(&self) -> Option<Arc<Material>> {
+        match self {
+            Shape::Triangle(triangle) => triangle.material.clone(),
+            Shape::Rectangle(rectangle) => rectangle.material.clone(),
+        }
+    }
+    /// Draws the shape with `material` instead of the default fragment shader.
+    pub fn set_material(&mut self, material: Option<Arc<Material>>) {
+        match self {
+            Shape::Triangle(triangle) => triangle.material = material,
+            Shape::Rectangle(rectangle) => rectangle.material = material,
+        }
+    }
+    pub fn get_layer(&self) -> i32 {
+        match self {
+            Shape::Triangle(triangle) => triangle.layer,
+            Shape::Rectangle(rectangle) => rectangle.layer,
+        }
+    }
+    pub fn set_layer(&mut self, layer: i32) {
+        match self {
+            Shape::Triangle(triangle) => triangle.layer = layer,
+            Shape::Rectangle(rectangle) => rectangle.layer = layer,
+        }
+    }
+    pub fn get_clip(&self) -> Option<usize> {
+        match self {
+            Shape::Triangle(triangle) => triangle.clip,
+            Shape::Rectangle(rectangle) => rectangle.clip,
+        }
+    }
+    pub fn set_clip(&mut self, clip: Option<usize>) {
+        match self {
+            Shape::Triangle(triangle) => triangle.clip = clip,
+            Shape::Rectangle(rectangle) => rectangle.clip = clip,
+        }
+    }
+    pub fn get_clip_rect(&self) -> Option<usize> {
+        match self {
+            Shape::Triangle(triangle) => triangle.clip_rect,
+            Shape::Rectangle(rectangle) => rectangle.clip_rect,
+        }
+    }
+    pub fn set_clip_rect(&mut self, clip_rect: Option<usize>) {
+        match self {
+            Shape::Triangle(triangle) => triangle.clip_rect = clip_rect,
+            Shape::Rectangle(rectangle) => rectangle.clip_rect = clip_rect,
+        }
+    }
     pub fn update_descriptor_set(&mut self, descriptor_set: Arc<DescriptorSet>) {
         match self {
             Shape::Triangle(triangle) => triangle.descriptor_set = Some(descriptor_set),
@@ -55,4 +107,22 @@ impl Shape {
             Shape::Rectangle(rectangle) => rectangle.vertices.clone(),
         }
     }
+    pub fn get_indices(&self) -> Vec<u32> {
+        match self {
+            Shape::Triangle(triangle) => triangle.indices.clone(),
+            Shape::Rectangle(rectangle) => rectangle.indices.clone(),
+        }
+    }
+    pub fn get_index_buffer(&self) -> Option<IndexBuffer> {
+        match self {
+            Shape::Triangle(triangle) => triangle.index_buffer.clone(),
+            Shape::Rectangle(rectangle) => rectangle.index_buffer.clone(),
+        }
+    }
+    pub fn update_index_buffer(&mut self, index_buffer: IndexBuffer) {
+        match self {
+            Shape::Triangle(triangle) => triangle.index_buffer = Some(index_buffer),
+            Shape::Rectangle(rectangle) => rectangle.index_buffer = Some(index_buffer),
+        }
+    }
 }
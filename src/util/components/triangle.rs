@@ -1,25 +1,46 @@
 use std::sync::Arc;
 
-use vulkano::{buffer::Subbuffer, descriptor_set::DescriptorSet};
+use vulkano::{
+    buffer::{IndexBuffer, Subbuffer},
+    descriptor_set::DescriptorSet,
+};
 
 use crate::util::vulkano::vulkano_utils::SimpleVertex;
 
+use super::material::Material;
+
 #[derive(Clone, Debug)]
 pub struct Triangle {
     pub vertices: Vec<SimpleVertex>,
+    pub indices: Vec<u32>,
     pub color: [f32; 4],
+    pub material: Option<Arc<Material>>,
+    /// Shapes on higher layers are drawn over lower ones, in insertion order within a layer.
+    pub layer: i32,
+    /// Clip mask the shape is drawn inside of, see `Vulkan::push_clip`.
+    pub clip: Option<usize>,
+    /// Clip rect the shape is drawn inside of, see `Vulkan::add_clip_rect`.
+    pub clip_rect: Option<usize>,
     pub descriptor_set: Option<Arc<DescriptorSet>>,
     pub vertex_buffer: Option<Subbuffer<[SimpleVertex]>>,
+    pub index_buffer: Option<IndexBuffer>,
 }
 
 impl Triangle {
     pub fn new(vertices: Vec<SimpleVertex>, color: [f32; 4]) -> Self {
+        let indices = (0..vertices.len() as u32).collect();
         Triangle {
             vertices,
+            indices,
             color,
-            // Descriptor set and vertex buffer generated automatically in vulkan initialization
+            material: None,
+            layer: 0,
+            clip: None,
+            clip_rect: None,
+            // Descriptor set, vertex and index buffers generated automatically in vulkan initialization
             descriptor_set: None,
             vertex_buffer: None,
+            index_buffer: None,
         }
     }
 }
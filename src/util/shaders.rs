@@ -1 +1,2 @@
+pub mod hot_reload;
 pub mod shaders;
@@ -0,0 +1,171 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::warn;
+use shaderc::{CompileOptions, Compiler, IncludeType, ResolvedInclude, ShaderKind};
+use vulkano::{
+    device::Device,
+    shader::{ShaderModule, ShaderModuleCreateInfo},
+};
+
+/// How often the shader files are checked for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Vertex and fragment shader, or the compiler output explaining why they failed to compile
+type CompiledShaders = Result<(Arc<ShaderModule>, Arc<ShaderModule>), String>;
+
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/util/shaders");
+// Included by the shaders, a change to it recompiles both
+const INCLUDES: &[&str] = &["output.glsl"];
+
+/// Compiles the GLSL sources of the embedded shaders at runtime whenever they change on disk.
+pub struct ShaderReloader {
+    compiler: Compiler,
+    vertex_shader: WatchedShader,
+    fragment_shader: WatchedShader,
+    includes: Vec<WatchedFile>,
+    last_poll: Instant,
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+struct WatchedShader {
+    file: WatchedFile,
+    kind: ShaderKind,
+}
+
+impl ShaderReloader {
+    /// Returns `None` when the shader compiler could not be initialized.
+    pub fn new() -> Option<Self> {
+        Some(ShaderReloader {
+            compiler: Compiler::new()?,
+            vertex_shader: WatchedShader::new("shape.vert", ShaderKind::Vertex),
+            fragment_shader: WatchedShader::new("shape.frag", ShaderKind::Fragment),
+            includes: INCLUDES.iter().map(|name| WatchedFile::new(name)).collect(),
+            last_poll: Instant::now(),
+        })
+    }
+
+    /// Recompiles the vertex and fragment shaders if either file changed since the last poll,
+    /// at most once per `POLL_INTERVAL`.
+    pub fn poll(&mut self, device: &Arc<Device>) -> Option<CompiledShaders> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        // Both are polled so that the modification times stay current
+        let vertex_changed = self.vertex_shader.file.poll();
+        let fragment_changed = self.fragment_shader.file.poll();
+        let mut include_changed = false;
+        for include in &mut self.includes {
+            include_changed |= include.poll();
+        }
+        if !vertex_changed && !fragment_changed && !include_changed {
+            return None;
+        }
+
+        Some(
+            self.vertex_shader
+                .compile(&self.compiler, device)
+                .and_then(|vs| {
+                    let fs = self.fragment_shader.compile(&self.compiler, device)?;
+                    Ok((vs, fs))
+                }),
+        )
+    }
+}
+
+impl WatchedFile {
+    fn new(file_name: &str) -> Self {
+        let path = Path::new(SHADER_DIR).join(file_name);
+        WatchedFile {
+            modified: modified(&path),
+            path,
+        }
+    }
+
+    fn poll(&mut self) -> bool {
+        let modified = modified(&self.path);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+}
+
+impl WatchedShader {
+    fn new(file_name: &str, kind: ShaderKind) -> Self {
+        WatchedShader {
+            file: WatchedFile::new(file_name),
+            kind,
+        }
+    }
+
+    fn compile(
+        &self,
+        compiler: &Compiler,
+        device: &Arc<Device>,
+    ) -> Result<Arc<ShaderModule>, String> {
+        let path = &self.file.path;
+        let source =
+            fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let mut options = CompileOptions::new().ok_or("failed to create compile options")?;
+        options.set_include_callback(resolve_include);
+        let artifact = compiler
+            .compile_into_spirv(
+                &source,
+                self.kind,
+                &path.to_string_lossy(),
+                "main",
+                Some(&options),
+            )
+            .map_err(|err| err.to_string())?;
+        if artifact.get_num_warnings() > 0 {
+            warn!(target: "shaders", "{}", artifact.get_warning_messages());
+        }
+
+        // Safety: the SPIR-V comes straight from the compiler, vulkano validates the rest
+        unsafe {
+            ShaderModule::new(
+                device.clone(),
+                ShaderModuleCreateInfo::new(artifact.as_binary()),
+            )
+        }
+        .map_err(|err| format!("{}: {err}", path.display()))
+    }
+}
+
+// `#include "file"` is looked up next to the including file like `vulkano_shaders::shader!`
+// does, `#include <file>` in the shader directory
+fn resolve_include(
+    requested: &str,
+    include_type: IncludeType,
+    requesting: &str,
+    _depth: usize,
+) -> Result<ResolvedInclude, String> {
+    let dir = match include_type {
+        IncludeType::Relative => Path::new(requesting)
+            .parent()
+            .unwrap_or(Path::new(SHADER_DIR)),
+        IncludeType::Standard => Path::new(SHADER_DIR),
+    };
+    let path = dir.join(requested);
+    let content = fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+    Ok(ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content,
+    })
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
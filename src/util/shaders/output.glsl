@@ -0,0 +1,59 @@
+// Included by the fragment shaders to write their colors for the swapchain.
+
+// Colors are given in sRGB, components above 1.0 are brighter than SDR white.
+// How they are written depends on the swapchain, see `OutputTransfer`.
+layout(constant_id = 0) const uint OUTPUT_TRANSFER = 0;
+const uint OUTPUT_SRGB = 0;
+const uint OUTPUT_LINEAR = 1;
+const uint OUTPUT_EXTENDED_LINEAR = 2;
+const uint OUTPUT_PQ = 3;
+
+// Brightness of SDR white on an HDR10 display, in nits
+const float SDR_WHITE_NITS = 203.0;
+
+vec3 srgb_to_linear(vec3 color) {
+    vec3 lower = color / 12.92;
+    vec3 higher = pow((color + 0.055) / 1.055, vec3(2.4));
+    return mix(higher, lower, lessThanEqual(color, vec3(0.04045)));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    vec3 lower = color * 12.92;
+    vec3 higher = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(higher, lower, lessThanEqual(color, vec3(0.0031308)));
+}
+
+// Scales colors brighter than SDR white back into range, keeping their hue
+vec3 tone_map(vec3 color) {
+    float peak = max(color.r, max(color.g, color.b));
+    return peak > 1.0 ? color / peak : color;
+}
+
+vec3 linear_to_pq(vec3 color) {
+    const mat3 bt709_to_bt2020 = mat3(
+        0.6274, 0.0691, 0.0164,
+        0.3293, 0.9195, 0.0880,
+        0.0433, 0.0114, 0.8956
+    );
+    vec3 y = max(bt709_to_bt2020 * color, 0.0) * (SDR_WHITE_NITS / 10000.0);
+    const float m1 = 2610.0 / 16384.0;
+    const float m2 = 2523.0 / 4096.0 * 128.0;
+    const float c1 = 3424.0 / 4096.0;
+    const float c2 = 2413.0 / 4096.0 * 32.0;
+    const float c3 = 2392.0 / 4096.0 * 32.0;
+    vec3 y_m1 = pow(y, vec3(m1));
+    return pow((c1 + c2 * y_m1) / (1.0 + c3 * y_m1), vec3(m2));
+}
+
+// Encodes a linear color for the swapchain
+vec3 encode_output(vec3 linear) {
+    if (OUTPUT_TRANSFER == OUTPUT_EXTENDED_LINEAR) {
+        return linear;
+    } else if (OUTPUT_TRANSFER == OUTPUT_PQ) {
+        return linear_to_pq(linear);
+    } else if (OUTPUT_TRANSFER == OUTPUT_LINEAR) {
+        return tone_map(linear);
+    } else {
+        return linear_to_srgb(tone_map(linear));
+    }
+}
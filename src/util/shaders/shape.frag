@@ -0,0 +1,20 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 1, binding = 0) uniform ColorUniform {
+    vec4 input_color;
+};
+
+layout(push_constant) uniform Node {
+    mat3x2 transform;
+    float opacity;
+} node;
+
+#include "output.glsl"
+
+void main() {
+    vec3 linear = srgb_to_linear(max(input_color.rgb, 0.0));
+    f_color = vec4(encode_output(linear), input_color.a * node.opacity);
+}
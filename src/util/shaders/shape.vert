@@ -0,0 +1,24 @@
+#version 460
+
+layout(location = 0) in vec2 position;
+
+layout(set = 0, binding = 0) uniform Globals {
+    float time;
+    uint frame;
+    vec2 resolution;
+    vec2 cursor;
+    float scale_factor;
+    // Moves the whole scene, in the same coordinates as the vertices
+    mat3x2 camera;
+};
+
+layout(push_constant) uniform Node {
+    // Transform of the node combined with its ancestors'
+    mat3x2 transform;
+    float opacity;
+} node;
+
+void main() {
+    vec2 world = node.transform * vec3(position, 1.0);
+    gl_Position = vec4(camera * vec3(world, 1.0), 0.0, 1.0);
+}
@@ -0,0 +1,35 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform Globals {
+    float time;
+    uint frame;
+    vec2 resolution;
+    vec2 cursor;
+    float scale_factor;
+    mat3x2 camera;
+};
+
+layout(set = 1, binding = 0) uniform ColorUniform {
+    vec4 input_color;
+};
+
+layout(push_constant) uniform Node {
+    mat3x2 transform;
+    float opacity;
+} node;
+
+layout(set = 2, binding = 0) uniform StripesParams {
+    // x: stripe width in logical pixels, y: brightness of the dark stripes
+    vec4 stripes;
+};
+
+#include "output.glsl"
+
+void main() {
+    float stripe = mod(floor((gl_FragCoord.x + gl_FragCoord.y) / (stripes.x * scale_factor)), 2.0);
+    vec3 linear = srgb_to_linear(max(input_color.rgb, 0.0) * mix(1.0, stripes.y, stripe));
+    f_color = vec4(encode_output(linear), input_color.a * node.opacity);
+}
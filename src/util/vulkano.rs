@@ -1 +1,6 @@
+pub mod color;
+pub mod debug;
+pub mod device_selection;
+pub mod pipeline_cache;
+pub mod upload;
 pub mod vulkano_utils;
@@ -0,0 +1,95 @@
+use vulkano::{
+    format::{Format, NumericFormat},
+    swapchain::ColorSpace,
+};
+
+// Brightness of SDR white on an HDR10 display, in nits
+const SDR_WHITE_NITS: f32 = 203.0;
+
+/// How colors are encoded before being written to the swapchain.
+///
+/// The discriminants are the values of the `OUTPUT_TRANSFER` specialization constant of
+/// `shaders/output.glsl`, whose `encode_output` does the same conversion as
+/// [`OutputTransfer::encode`] for every fragment shader including it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputTransfer {
+    /// UNORM format with an sRGB color space, colors are written sRGB encoded.
+    Srgb = 0,
+    /// sRGB format, the hardware encodes the linear colors written by the shader.
+    Linear = 1,
+    /// scRGB, linear colors where 1.0 is SDR white and brighter values are kept.
+    ExtendedLinear = 2,
+    /// HDR10, BT.2020 primaries with the ST 2084 (PQ) curve.
+    Pq = 3,
+}
+
+impl OutputTransfer {
+    pub fn new(format: Format, color_space: ColorSpace) -> Self {
+        match color_space {
+            ColorSpace::ExtendedSrgbLinear => OutputTransfer::ExtendedLinear,
+            ColorSpace::Hdr10St2084 => OutputTransfer::Pq,
+            _ if format.numeric_format_color() == Some(NumericFormat::SRGB) => {
+                OutputTransfer::Linear
+            }
+            _ => OutputTransfer::Srgb,
+        }
+    }
+
+    /// Converts an sRGB color, which may exceed 1.0, to the values written to the swapchain.
+    /// Outputs without HDR support tone map colors brighter than SDR white.
+    pub fn encode(self, color: [f32; 4]) -> [f32; 4] {
+        let linear = [color[0], color[1], color[2]].map(|c| srgb_to_linear(c.max(0.0)));
+        let [r, g, b] = match self {
+            OutputTransfer::ExtendedLinear => linear,
+            OutputTransfer::Pq => linear_to_pq(linear),
+            OutputTransfer::Linear => tone_map(linear),
+            OutputTransfer::Srgb => tone_map(linear).map(linear_to_srgb),
+        };
+        [r, g, b, color[3]]
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Scales colors brighter than SDR white back into range, keeping their hue
+fn tone_map(color: [f32; 3]) -> [f32; 3] {
+    let peak = color[0].max(color[1]).max(color[2]);
+    if peak > 1.0 {
+        color.map(|c| c / peak)
+    } else {
+        color
+    }
+}
+
+fn linear_to_pq(color: [f32; 3]) -> [f32; 3] {
+    const BT709_TO_BT2020: [[f32; 3]; 3] = [
+        [0.6274, 0.3293, 0.0433],
+        [0.0691, 0.9195, 0.0114],
+        [0.0164, 0.0880, 0.8956],
+    ];
+    const M1: f32 = 2610.0 / 16384.0;
+    const M2: f32 = 2523.0 / 4096.0 * 128.0;
+    const C1: f32 = 3424.0 / 4096.0;
+    const C2: f32 = 2413.0 / 4096.0 * 32.0;
+    const C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+    BT709_TO_BT2020.map(|row| {
+        let c = row[0] * color[0] + row[1] * color[1] + row[2] * color[2];
+        let y = (c.max(0.0) * SDR_WHITE_NITS / 10000.0).powf(M1);
+        ((C1 + C2 * y) / (1.0 + C3 * y)).powf(M2)
+    })
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use log::{Level, log, warn};
+use vulkano::{
+    VulkanLibrary, VulkanObject,
+    device::{Device, DeviceOwned},
+    instance::{
+        Instance, InstanceExtensions,
+        debug::{
+            DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger,
+            DebugUtilsMessengerCallback, DebugUtilsMessengerCreateInfo,
+        },
+    },
+};
+
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+/// Layers to enable for debugging, empty when the validation layer is not installed.
+pub fn validation_layers(library: &Arc<VulkanLibrary>) -> Vec<String> {
+    let available = library
+        .layer_properties()
+        .map(|mut layers| layers.any(|layer| layer.name() == VALIDATION_LAYER))
+        .unwrap_or(false);
+    if !available {
+        warn!(target: "device", "{VALIDATION_LAYER} is not installed, continuing without validation");
+        return Vec::new();
+    }
+    vec![VALIDATION_LAYER.to_owned()]
+}
+
+/// Instance extensions needed for debugging that `library` and `layers` provide.
+pub fn debug_extensions(library: &Arc<VulkanLibrary>, layers: &[String]) -> InstanceExtensions {
+    let extensions = InstanceExtensions {
+        ext_debug_utils: true,
+        ..InstanceExtensions::empty()
+    };
+    library
+        .supported_extensions_with_layers(layers.iter().map(String::as_str))
+        .map(|supported_extensions| extensions.intersection(&supported_extensions))
+        .unwrap_or_default()
+}
+
+/// Logs every message at or above `min_severity` reported by the driver and layers.
+pub fn create_debug_messenger(
+    instance: &Arc<Instance>,
+    min_severity: DebugUtilsMessageSeverity,
+) -> Option<DebugUtilsMessenger> {
+    if !instance.enabled_extensions().ext_debug_utils {
+        return None;
+    }
+
+    let message_severity = [
+        DebugUtilsMessageSeverity::VERBOSE,
+        DebugUtilsMessageSeverity::INFO,
+        DebugUtilsMessageSeverity::WARNING,
+        DebugUtilsMessageSeverity::ERROR,
+    ]
+    .into_iter()
+    .skip_while(|severity| !min_severity.intersects(*severity))
+    .fold(DebugUtilsMessageSeverity::empty(), |all, severity| {
+        all | severity
+    });
+
+    // The callback must not call into Vulkan, it only forwards the message to the logger
+    let user_callback = unsafe {
+        DebugUtilsMessengerCallback::new(|severity, message_type, data| {
+            let level = if severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+                Level::Error
+            } else if severity.intersects(DebugUtilsMessageSeverity::WARNING) {
+                Level::Warn
+            } else if severity.intersects(DebugUtilsMessageSeverity::INFO) {
+                Level::Debug
+            } else {
+                Level::Trace
+            };
+            let message_type = if message_type.intersects(DebugUtilsMessageType::VALIDATION) {
+                "validation"
+            } else if message_type.intersects(DebugUtilsMessageType::PERFORMANCE) {
+                "performance"
+            } else {
+                "general"
+            };
+            log!(
+                target: "device",
+                level,
+                "[{message_type}] {}: {}",
+                data.message_id_name.unwrap_or("unknown"),
+                data.message
+            );
+        })
+    };
+
+    DebugUtilsMessenger::new(
+        instance.clone(),
+        DebugUtilsMessengerCreateInfo {
+            message_severity,
+            message_type: DebugUtilsMessageType::GENERAL
+                | DebugUtilsMessageType::VALIDATION
+                | DebugUtilsMessageType::PERFORMANCE,
+            ..DebugUtilsMessengerCreateInfo::user_callback(user_callback)
+        },
+    )
+    .ok()
+}
+
+/// Gives `object` a name that shows up in validation messages and graphics debuggers.
+/// Does nothing unless debugging is enabled.
+pub fn set_object_name<T: VulkanObject + DeviceOwned>(
+    device: &Arc<Device>,
+    object: &T,
+    name: &str,
+) {
+    if device.instance().enabled_extensions().ext_debug_utils {
+        let _ = device.set_debug_utils_object_name(object, Some(name));
+    }
+}
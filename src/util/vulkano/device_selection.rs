@@ -0,0 +1,184 @@
+use std::{env, sync::Arc};
+
+use log::debug;
+use vulkano::{
+    Version,
+    device::{
+        DeviceExtensions, QueueFlags,
+        physical::{PhysicalDevice, PhysicalDeviceType},
+    },
+    image::SampleCount,
+    instance::Instance,
+    swapchain::Surface,
+};
+
+/// Environment variable overriding `VulkanSettings::device`, parsed by [`DeviceSelector::parse`].
+pub const DEVICE_ENV_VAR: &str = "VULKAN_DEVICE";
+
+/// Which physical device `Vulkan` renders with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeviceSelector {
+    /// The first usable device, preferring discrete over integrated, virtual and CPU devices.
+    #[default]
+    Auto,
+    /// The first usable device whose name contains the string, ignoring case.
+    Name(String),
+    /// The device at this position in the enumeration order, see [`list_devices`].
+    Index(usize),
+    /// The first usable device of this type.
+    Type(PhysicalDeviceType),
+}
+
+impl DeviceSelector {
+    /// Reads the selector from the `VULKAN_DEVICE` environment variable, if set.
+    pub fn from_env() -> Option<Self> {
+        env::var(DEVICE_ENV_VAR)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| Self::parse(&value))
+    }
+
+    /// Parses `auto`, a device index, a device type (`discrete`, `integrated`, `virtual`, `cpu`)
+    /// or otherwise a name substring.
+    pub fn parse(value: &str) -> Self {
+        if let Ok(index) = value.parse() {
+            return DeviceSelector::Index(index);
+        }
+        match value.to_lowercase().as_str() {
+            "auto" => DeviceSelector::Auto,
+            "discrete" => DeviceSelector::Type(PhysicalDeviceType::DiscreteGpu),
+            "integrated" => DeviceSelector::Type(PhysicalDeviceType::IntegratedGpu),
+            "virtual" => DeviceSelector::Type(PhysicalDeviceType::VirtualGpu),
+            "cpu" => DeviceSelector::Type(PhysicalDeviceType::Cpu),
+            _ => DeviceSelector::Name(value.to_owned()),
+        }
+    }
+
+    fn matches(&self, candidate: &DeviceCandidate) -> bool {
+        match self {
+            DeviceSelector::Auto => true,
+            DeviceSelector::Name(name) => {
+                candidate.name.to_lowercase().contains(&name.to_lowercase())
+            }
+            DeviceSelector::Index(index) => candidate.index == *index,
+            DeviceSelector::Type(device_type) => candidate.device_type == *device_type,
+        }
+    }
+}
+
+/// A physical device seen while selecting where to render.
+#[derive(Clone, Debug)]
+pub struct DeviceCandidate {
+    pub physical_device: Arc<PhysicalDevice>,
+    pub index: usize,
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    pub api_version: Version,
+    pub driver_name: Option<String>,
+    pub max_samples: SampleCount,
+    /// Queue family able to draw and present to the surface.
+    pub queue_family_index: Option<u32>,
+    /// Why the device cannot be used, `None` if it can.
+    pub rejection: Option<String>,
+}
+
+/// Lists every physical device of `instance`, with the reason it can't render to `surface`.
+pub fn list_devices(
+    instance: &Arc<Instance>,
+    surface: &Arc<Surface>,
+    device_extensions: &DeviceExtensions,
+) -> Vec<DeviceCandidate> {
+    instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .enumerate()
+        .map(|(index, p)| {
+            let properties = p.properties();
+            let queue_family_index = p
+                .queue_family_properties()
+                .iter()
+                .enumerate()
+                .position(|(i, q)| {
+                    q.queue_flags.contains(QueueFlags::GRAPHICS)
+                        && p.surface_support(i as u32, surface).unwrap_or(false)
+                })
+                .map(|q| q as u32);
+
+            let missing_extensions = device_extensions.difference(p.supported_extensions());
+            let rejection = if !missing_extensions.is_empty() {
+                Some(format!("missing extensions {missing_extensions:?}"))
+            } else if queue_family_index.is_none() {
+                Some("no queue family can draw and present to the surface".to_owned())
+            } else {
+                None
+            };
+
+            DeviceCandidate {
+                index,
+                name: properties.device_name.clone(),
+                device_type: properties.device_type,
+                api_version: properties.api_version,
+                driver_name: properties.driver_name.clone(),
+                max_samples: properties.framebuffer_color_sample_counts.max_count(),
+                queue_family_index,
+                rejection,
+                physical_device: p,
+            }
+        })
+        .collect()
+}
+
+/// Picks the device matching `selector`. Panics when no usable device matches, so that a run
+/// pinned to a device doesn't silently render with another one.
+pub fn select_physical_device(
+    instance: &Arc<Instance>,
+    surface: &Arc<Surface>,
+    device_extensions: &DeviceExtensions,
+    selector: &DeviceSelector,
+) -> (Arc<PhysicalDevice>, u32) {
+    let candidates = list_devices(instance, surface, device_extensions);
+    for candidate in candidates.iter() {
+        debug!(
+            target: "device",
+            "[{}] {} ({:?}, Vulkan {}, driver {}, up to {:?}){}",
+            candidate.index,
+            candidate.name,
+            candidate.device_type,
+            candidate.api_version,
+            candidate.driver_name.as_deref().unwrap_or("unknown"),
+            candidate.max_samples,
+            match &candidate.rejection {
+                Some(rejection) => format!(": rejected, {rejection}"),
+                None => String::new(),
+            }
+        );
+    }
+
+    let selected = candidates
+        .iter()
+        .filter(|candidate| candidate.rejection.is_none() && selector.matches(candidate))
+        .min_by_key(|candidate| device_type_rank(candidate.device_type))
+        .unwrap_or_else(|| {
+            let usable: Vec<String> = candidates
+                .iter()
+                .filter(|candidate| candidate.rejection.is_none())
+                .map(|candidate| format!("[{}] {}", candidate.index, candidate.name))
+                .collect();
+            panic!("no usable device matches {selector:?}, usable devices: {usable:?}")
+        });
+
+    (
+        selected.physical_device.clone(),
+        selected.queue_family_index.unwrap(),
+    )
+}
+
+fn device_type_rank(device_type: PhysicalDeviceType) -> u32 {
+    match device_type {
+        PhysicalDeviceType::DiscreteGpu => 0,
+        PhysicalDeviceType::IntegratedGpu => 1,
+        PhysicalDeviceType::VirtualGpu => 2,
+        PhysicalDeviceType::Cpu => 3,
+        _ => 4,
+    }
+}
@@ -0,0 +1,95 @@
+use std::{fs, path::PathBuf, process, sync::Arc};
+
+use log::{debug, info, warn};
+use vulkano::{
+    device::Device,
+    pipeline::cache::{PipelineCache, PipelineCacheCreateInfo},
+};
+
+// VK_PIPELINE_CACHE_HEADER_VERSION_ONE: length, version, vendor ID, device ID, then the UUID
+const HEADER_VERSION_ONE: u32 = 1;
+const HEADER_LENGTH: usize = 16 + 16;
+
+/// File the pipeline cache is kept in, under the user cache directory.
+pub fn pipeline_cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("pipeline_cache.bin"))
+}
+
+/// Creates the pipeline cache, filled with the data saved by a previous run if it was written
+/// by the same device and driver.
+pub fn load_pipeline_cache(device: &Arc<Device>) -> Arc<PipelineCache> {
+    let initial_data = pipeline_cache_path()
+        .and_then(|path| fs::read(&path).ok().map(|data| (path, data)))
+        .filter(|(path, data)| match validate_header(device, data) {
+            Ok(()) => true,
+            Err(reason) => {
+                info!(target: "device", "Ignoring pipeline cache {}: {reason}", path.display());
+                false
+            }
+        })
+        .map(|(path, data)| {
+            debug!(target: "device", "Loaded {} bytes of pipeline cache from {}", data.len(), path.display());
+            data
+        })
+        .unwrap_or_default();
+
+    // Safety: the header was checked against this device, the driver validates the rest
+    unsafe {
+        PipelineCache::new(
+            device.clone(),
+            PipelineCacheCreateInfo {
+                initial_data,
+                ..Default::default()
+            },
+        )
+    }
+    .expect("failed to create pipeline cache")
+}
+
+/// Writes the contents of `pipeline_cache` to disk for the next run to load.
+pub fn save_pipeline_cache(pipeline_cache: &PipelineCache) {
+    let Some(path) = pipeline_cache_path() else {
+        return;
+    };
+    let result = pipeline_cache
+        .get_data()
+        .map_err(|err| err.to_string())
+        .and_then(|data| {
+            fs::create_dir_all(path.parent().unwrap()).map_err(|err| err.to_string())?;
+            // Renamed over the cache so a crash or another instance never leaves it truncated
+            let temp_path = path.with_extension(format!("tmp{}", process::id()));
+            fs::write(&temp_path, &data).map_err(|err| err.to_string())?;
+            fs::rename(&temp_path, &path).map_err(|err| {
+                let _ = fs::remove_file(&temp_path);
+                err.to_string()
+            })?;
+            Ok(data.len())
+        });
+    match result {
+        Ok(len) => {
+            debug!(target: "device", "Saved {len} bytes of pipeline cache to {}", path.display())
+        }
+        Err(err) => {
+            warn!(target: "device", "Could not save pipeline cache to {}: {err}", path.display())
+        }
+    }
+}
+
+fn validate_header(device: &Arc<Device>, data: &[u8]) -> Result<(), String> {
+    if data.len() < HEADER_LENGTH {
+        return Err("header is truncated".to_owned());
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let properties = device.physical_device().properties();
+
+    if (read_u32(0) as usize) < HEADER_LENGTH || read_u32(4) != HEADER_VERSION_ONE {
+        return Err("unknown header version".to_owned());
+    }
+    if read_u32(8) != properties.vendor_id || read_u32(12) != properties.device_id {
+        return Err("written by another device".to_owned());
+    }
+    if data[16..32] != properties.pipeline_cache_uuid {
+        return Err("written by another driver version".to_owned());
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use vulkano::{
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer},
+    command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, PrimaryAutoCommandBuffer,
+        PrimaryCommandBufferAbstract, allocator::StandardCommandBufferAllocator,
+    },
+    device::Queue,
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    sync::{GpuFuture, Sharing},
+};
+
+/// Copies static data into device-local buffers through host-visible staging buffers.
+///
+/// Copies are recorded into a single transfer command buffer and submitted together by
+/// [`Uploader::flush`], which blocks until the transfer queue has finished.
+pub struct Uploader {
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    transfer_queue: Arc<Queue>,
+    graphics_queue_family_index: u32,
+    builder: Option<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>>,
+}
+
+impl Uploader {
+    pub fn new(
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
+        transfer_queue: &Arc<Queue>,
+        graphics_queue: &Arc<Queue>,
+    ) -> Self {
+        Uploader {
+            memory_allocator: memory_allocator.clone(),
+            command_buffer_allocator: command_buffer_allocator.clone(),
+            transfer_queue: transfer_queue.clone(),
+            graphics_queue_family_index: graphics_queue.queue_family_index(),
+            builder: None,
+        }
+    }
+
+    /// Creates a device-local buffer with the given usage and records a copy of `data` into it.
+    ///
+    /// The returned buffer must not be read before [`Uploader::flush`] has been called.
+    pub fn upload_iter<T, I>(&mut self, usage: BufferUsage, data: I) -> Subbuffer<[T]>
+    where
+        T: BufferContents,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let staging_buffer = Buffer::from_iter(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            data,
+        )
+        .unwrap();
+
+        let device_buffer = Buffer::new_slice::<T>(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: usage | BufferUsage::TRANSFER_DST,
+                sharing: match self.concurrent_queue_family_indices() {
+                    Some(queue_family_indices) => {
+                        Sharing::Concurrent(queue_family_indices.into_iter().collect())
+                    }
+                    None => Sharing::Exclusive,
+                },
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            staging_buffer.len(),
+        )
+        .unwrap();
+
+        self.builder()
+            .copy_buffer(CopyBufferInfo::buffers(
+                staging_buffer,
+                device_buffer.clone(),
+            ))
+            .unwrap();
+
+        device_buffer
+    }
+
+    /// Submits every recorded copy to the transfer queue and waits for it to complete.
+    pub fn flush(&mut self) {
+        let Some(builder) = self.builder.take() else {
+            return;
+        };
+
+        builder
+            .build()
+            .unwrap()
+            .execute(self.transfer_queue.clone())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+    }
+
+    fn builder(&mut self) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        self.builder.get_or_insert_with(|| {
+            AutoCommandBufferBuilder::primary(
+                self.command_buffer_allocator.clone(),
+                self.transfer_queue.queue_family_index(),
+                CommandBufferUsage::OneTimeSubmit,
+            )
+            .unwrap()
+        })
+    }
+
+    // Buffers written on a dedicated transfer queue are read on the graphics queue, so they
+    // are shared between both families instead of doing an explicit ownership transfer.
+    fn concurrent_queue_family_indices(&self) -> Option<[u32; 2]> {
+        let transfer_queue_family_index = self.transfer_queue.queue_family_index();
+        if transfer_queue_family_index == self.graphics_queue_family_index {
+            None
+        } else {
+            Some([
+                self.graphics_queue_family_index,
+                transfer_queue_family_index,
+            ])
+        }
+    }
+}
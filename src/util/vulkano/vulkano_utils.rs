@@ -1,46 +1,71 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    iter,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 type FenceFuture = FenceSignalFuture<
     PresentFuture<CommandBufferExecFuture<JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture>>>,
 >;
 
+use log::{debug, error, info, warn};
 use vulkano::{
     Validated, VulkanError, VulkanLibrary,
-    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage},
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, IndexBuffer, Subbuffer},
     command_buffer::{
         AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage,
         PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassBeginInfo, SubpassContents,
         SubpassEndInfo, allocator::StandardCommandBufferAllocator,
     },
     descriptor_set::{
-        DescriptorSet, WriteDescriptorSet, allocator::StandardDescriptorSetAllocator,
+        DescriptorSet, WriteDescriptorSet,
+        allocator::StandardDescriptorSetAllocator,
+        layout::{
+            DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo,
+            DescriptorType,
+        },
     },
     device::{
-        Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
-        physical::{PhysicalDevice, PhysicalDeviceType},
+        Device, DeviceCreateInfo, DeviceExtensions, DeviceOwned, Queue, QueueCreateInfo,
+        QueueFlags, physical::PhysicalDevice,
+    },
+    format::{ClearValue, Format, FormatFeatures},
+    image::{
+        Image, ImageAspects, ImageCreateInfo, ImageType, ImageUsage, SampleCount, view::ImageView,
+    },
+    instance::{
+        Instance, InstanceCreateFlags, InstanceCreateInfo, InstanceExtensions,
+        debug::{DebugUtilsMessageSeverity, DebugUtilsMessenger},
     },
-    format::{ClearValue, Format},
-    image::{Image, ImageCreateInfo, ImageType, ImageUsage, SampleCount, view::ImageView},
-    instance::{Instance, InstanceCreateFlags, InstanceCreateInfo},
     memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
     pipeline::{
-        GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+        DynamicState, GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout,
         PipelineShaderStageCreateInfo,
+        cache::PipelineCache,
         graphics::{
             GraphicsPipelineCreateInfo,
-            color_blend::{ColorBlendAttachmentState, ColorBlendState},
+            color_blend::{
+                AttachmentBlend, BlendFactor, ColorBlendAttachmentState, ColorBlendState,
+                ColorComponents,
+            },
+            depth_stencil::{
+                CompareOp, DepthStencilState, StencilFaces, StencilOp, StencilOpState, StencilOps,
+                StencilState,
+            },
             input_assembly::InputAssemblyState,
             multisample::MultisampleState,
             rasterization::RasterizationState,
             vertex_input::{Vertex, VertexDefinition, VertexInputState},
-            viewport::{Viewport, ViewportState},
+            viewport::{Scissor, Viewport, ViewportState},
         },
-        layout::PipelineDescriptorSetLayoutCreateInfo,
+        layout::{PipelineLayoutCreateInfo, PushConstantRange},
     },
     render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass},
+    shader::{EntryPoint, ShaderModule, ShaderStages, SpecializationConstant},
     swapchain::{
-        self, ColorSpace, PresentFuture, PresentMode, Surface, Swapchain, SwapchainAcquireFuture,
-        SwapchainCreateInfo, SwapchainPresentInfo,
+        self, ColorSpace, CompositeAlpha, CompositeAlphas, PresentFuture, PresentMode, Surface,
+        Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo, SwapchainPresentInfo,
     },
     sync::{
         self, GpuFuture,
@@ -50,29 +75,257 @@ use vulkano::{
 use winit::window::Window;
 
 use crate::util::{
-    components::shape::Shape,
-    shaders::shaders::{fragment_shader, vertex_shader},
+    components::{
+        material::Material,
+        scene::{self, Affine, IDENTITY_MATRIX, Node, NodeId, RenderItem, SceneGraph, Transform},
+        shape::Shape,
+    },
+    shaders::{
+        hot_reload::ShaderReloader,
+        shaders::{fragment_shader, vertex_shader},
+    },
+    vulkano::{
+        color::OutputTransfer,
+        debug::{create_debug_messenger, debug_extensions, set_object_name, validation_layers},
+        device_selection::{DeviceCandidate, DeviceSelector, list_devices, select_physical_device},
+        pipeline_cache::{load_pipeline_cache, save_pipeline_cache},
+        upload::Uploader,
+    },
 };
 
+/// How `Vulkan::redraw` obtains the command buffer it submits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingMode {
+    /// One command buffer per framebuffer, recorded up front and reused every frame.
+    Prebaked,
+    /// A one-time-submit command buffer recorded every frame from the current elements.
+    PerFrame,
+}
+
+/// Which surface formats `create_swapchain` prefers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatPolicy {
+    /// 8 bits per channel, sRGB encoded when available to avoid banding.
+    Quality,
+    /// The smallest format available, trading color depth for bandwidth.
+    Memory,
+    /// HDR10 or scRGB output when the surface offers an HDR color space, otherwise 10-bit or
+    /// floating point SDR formats, falling back to `FormatPolicy::Quality`. Shape colors above
+    /// 1.0 are tone mapped when HDR output is not available.
+    Hdr,
+}
+
+const CLEAR_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+/// Options chosen when creating a `Vulkan` renderer.
+#[derive(Clone, Debug)]
+pub struct VulkanSettings {
+    /// Present modes in order of preference, the first one supported by the surface is used.
+    /// `PresentMode::Fifo` is always supported and used when none of them are.
+    pub present_modes: Vec<PresentMode>,
+    pub format_policy: FormatPolicy,
+    pub samples: SampleCount,
+    pub recording_mode: RecordingMode,
+    pub frames_in_flight: usize,
+    /// Enables the Khronos validation layer when installed, prints driver messages at or above
+    /// `debug_severity` and names Vulkan objects after what they hold.
+    pub debug: bool,
+    pub debug_severity: DebugUtilsMessageSeverity,
+    /// Overridden by the `VULKAN_DEVICE` environment variable, see `DeviceSelector::parse`.
+    pub device: DeviceSelector,
+    /// Compiles the GLSL files under `src/util/shaders` at runtime and rebuilds the pipeline
+    /// whenever they change, see `Vulkan::poll_shaders`.
+    pub shader_hot_reload: bool,
+    /// Lets the desktop show through the window where it is not opaque, using premultiplied
+    /// or postmultiplied composite alpha when the surface supports it. The window must be
+    /// created transparent as well.
+    pub transparent: bool,
+    /// Color in sRGB the frame is cleared to, its alpha only matters with `transparent`.
+    pub clear_color: [f32; 4],
+}
+
+impl Default for VulkanSettings {
+    fn default() -> Self {
+        VulkanSettings {
+            present_modes: vec![
+                PresentMode::Mailbox,
+                PresentMode::Immediate,
+                PresentMode::FifoRelaxed,
+                PresentMode::Fifo,
+            ],
+            format_policy: FormatPolicy::Quality,
+            samples: SampleCount::Sample4,
+            recording_mode: RecordingMode::Prebaked,
+            frames_in_flight: 2,
+            debug: false,
+            debug_severity: DebugUtilsMessageSeverity::WARNING,
+            device: DeviceSelector::Auto,
+            shader_hot_reload: false,
+            transparent: false,
+            clear_color: CLEAR_COLOR,
+        }
+    }
+}
+
+/// Upper bound for the number of frames the CPU may record ahead of the GPU.
+pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
+
+/// Timings measured during the most recent `Vulkan::redraw`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameLatency {
+    /// Time the CPU spent blocked until the GPU released the resources of the frame slot.
+    pub cpu_wait: Duration,
+    /// Time between submitting the most recently finished frame and observing its fence
+    /// signaled. Fences are checked at the start of every redraw, so this is accurate to the
+    /// time between redraws.
+    pub gpu_latency: Duration,
+}
+
+// Everything owned by a single frame in flight. The semaphores between acquire, render and
+// present are created by the future chain of each submission, the fence guards the rest.
+struct FrameResources {
+    fence: Option<Arc<FenceFuture>>,
+    submitted_at: Option<Instant>,
+    // Copies of every element's uniforms, indexed like `Vulkan::elements`
+    color_buffers: Vec<Subbuffer<ColorUniform>>,
+    descriptor_sets: Vec<Arc<DescriptorSet>>,
+}
+
+/// Pipeline built for a `Material`, with the descriptor set holding its parameters.
+struct MaterialPipeline {
+    pipeline: Arc<GraphicsPipeline>,
+    params: Option<Arc<DescriptorSet>>,
+}
+
+/// What a `Material` draws with, materials with the same shader and parameters share a
+/// `MaterialPipeline` whatever their names.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct MaterialKey {
+    // Address of the loader, the same for every material of a shader
+    fragment_shader: usize,
+    params: Vec<[u32; 4]>,
+}
+
+impl MaterialKey {
+    fn new(material: &Material) -> Self {
+        MaterialKey {
+            fragment_shader: material.fragment_shader as usize,
+            params: material
+                .params
+                .iter()
+                .map(|param| param.map(f32::to_bits))
+                .collect(),
+        }
+    }
+}
+
+/// Shape whose area clips the elements added between `Vulkan::push_clip` and `Vulkan::pop_clip`.
+struct ClipMask {
+    shape: Shape,
+    // Clip that was active when this one was pushed
+    parent: Option<usize>,
+    // Node placing the mask, its transform is refreshed before recording
+    node: NodeId,
+    transform: Affine,
+}
+
+// Pipelines drawing clip masks into the stencil buffer without touching the color attachments
+struct ClipPipelines {
+    push: Arc<GraphicsPipeline>,
+    pop: Arc<GraphicsPipeline>,
+}
+
+// How a pipeline tests and updates the stencil buffer holding the clip masks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StencilMode {
+    Draw,
+    PushClip,
+    PopClip,
+}
+
 pub struct Vulkan {
     swapchain: Arc<Swapchain>,
     render_pass: Arc<RenderPass>,
     viewport: Viewport,
+    physical_device: Arc<PhysicalDevice>,
+    surface: Arc<Surface>,
     device: Arc<Device>,
+    present_modes: Vec<PresentMode>,
+    framebuffers: Vec<Arc<Framebuffer>>,
     command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
     queue: Arc<Queue>,
+    uploader: Uploader,
     elements: Vec<Shape>,
-    fences: Vec<Option<Arc<FenceFuture>>>,
+    scene: SceneGraph,
+    // Set by `node_mut` and `element_mut` so that prebaked command buffers are recorded again
+    // before drawing
+    scene_changed: bool,
+    image_fences: Vec<Option<Arc<FenceFuture>>>,
     memory_allocator: Arc<StandardMemoryAllocator>,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
-    previous_fence: u32,
+    descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+    current_frame: usize,
+    previous_frame: usize,
+    latency: FrameLatency,
+    pipeline: Arc<GraphicsPipeline>,
+    clip_pipelines: ClipPipelines,
+    pipeline_cache: Arc<PipelineCache>,
+    shader_reloader: Option<ShaderReloader>,
     stages: [PipelineShaderStageCreateInfo; 2],
     vertex_input_state: VertexInputState,
     layout: Arc<PipelineLayout>,
+    material_layout: Arc<PipelineLayout>,
+    // Keyed by `MaterialKey::fragment_shader`, `None` for shaders that failed to build
+    material_pipelines: HashMap<usize, Option<Arc<GraphicsPipeline>>>,
+    // `None` for materials that failed to build, drawn with the default pipeline
+    materials: HashMap<MaterialKey, Option<MaterialPipeline>>,
     multisample_state: MultisampleState,
+    stencil_format: Format,
+    clips: Vec<ClipMask>,
+    // Clips applied to elements as they are added, innermost last
+    clip_stack: Vec<usize>,
+    clip_rects: Vec<Scissor>,
+    output_transfer: OutputTransfer,
+    clear_color: [f32; 4],
+    recording_mode: RecordingMode,
+    frame_resources: Vec<FrameResources>,
+    global_buffers: Vec<Subbuffer<GlobalUniform>>,
+    global_descriptor_sets: Vec<Arc<DescriptorSet>>,
+    started_at: Instant,
+    frame_count: u32,
+    cursor_position: [f32; 2],
+    scale_factor: f32,
+    camera: Transform,
+    _debug_messenger: Option<DebugUtilsMessenger>,
 }
 impl Vulkan {
     pub fn redraw(&mut self) -> bool {
+        let frame = self.current_frame;
+
+        // Frames that finished since the last redraw, the latest submitted one sets the latency
+        let mut latest_submission = None;
+        for frame_resources in &mut self.frame_resources {
+            if let Some(fence) = &frame_resources.fence
+                && fence.is_signaled().unwrap_or(false)
+                && let Some(submitted_at) = frame_resources.submitted_at.take()
+            {
+                latest_submission = latest_submission.max(Some(submitted_at));
+            }
+        }
+        if let Some(submitted_at) = latest_submission {
+            self.latency.gpu_latency = submitted_at.elapsed();
+        }
+
+        // Wait until the GPU is done with the last submission that used this frame slot
+        let wait_start = Instant::now();
+        if let Some(frame_fence) = &self.frame_resources[frame].fence {
+            frame_fence.wait(None).unwrap();
+        }
+        self.latency.cpu_wait = wait_start.elapsed();
+        if let Some(submitted_at) = self.frame_resources[frame].submitted_at.take() {
+            self.latency.gpu_latency = submitted_at.elapsed();
+        }
+
         let swapchain = self.swapchain.clone();
         let mut recreate_swapchain = false;
         let (image_i, suboptimal, acquire_future) =
@@ -88,11 +341,33 @@ impl Vulkan {
         if suboptimal {
             recreate_swapchain = true;
         }
-        if let Some(image_fence) = &self.fences[image_i as usize] {
+        // The image may still be rendered to by a different frame slot
+        if let Some(image_fence) = &self.image_fences[image_i as usize] {
             image_fence.wait(None).unwrap();
         }
 
-        let previous_future = match self.fences[self.previous_fence as usize].clone() {
+        *self.global_buffers[image_i as usize].write().unwrap() = GlobalUniform {
+            time: self.started_at.elapsed().as_secs_f32(),
+            frame: self.frame_count,
+            resolution: self.viewport.extent,
+            cursor: self.cursor_position,
+            scale_factor: self.scale_factor,
+            _padding: 0.0,
+            camera: self.camera_matrix().map(|[x, y]| [x, y, 0.0, 0.0]),
+        };
+        self.frame_count = self.frame_count.wrapping_add(1);
+
+        if self.scene_changed {
+            self.scene_changed = false;
+            self.rebuild_command_buffers();
+        }
+
+        let command_buffer = match self.recording_mode {
+            RecordingMode::Prebaked => self.command_buffers[image_i as usize].clone(),
+            RecordingMode::PerFrame => self.record_frame(frame, image_i as usize),
+        };
+
+        let previous_future = match self.frame_resources[self.previous_frame].fence.clone() {
             None => {
                 let mut now = sync::now(self.device.clone());
                 now.cleanup_finished();
@@ -103,10 +378,7 @@ impl Vulkan {
         };
         let future = previous_future
             .join(acquire_future)
-            .then_execute(
-                self.queue.clone(),
-                self.command_buffers[image_i as usize].clone(),
-            )
+            .then_execute(self.queue.clone(), command_buffer)
             .unwrap()
             .then_swapchain_present(
                 self.queue.clone(),
@@ -114,20 +386,71 @@ impl Vulkan {
             )
             .then_signal_fence_and_flush();
 
-        self.fences[image_i as usize] = match future.map_err(Validated::unwrap) {
+        let fence = match future.map_err(Validated::unwrap) {
             Ok(value) => Some(Arc::new(value)),
             Err(VulkanError::OutOfDate) => {
                 recreate_swapchain = true;
                 None
             }
             Err(e) => {
-                println!("failed to flush future: {e}");
+                error!(target: "swapchain", "failed to flush future: {e}");
                 None
             }
         };
-        self.previous_fence = image_i;
+        self.frame_resources[frame].submitted_at = fence.as_ref().map(|_| Instant::now());
+        self.frame_resources[frame].fence = fence.clone();
+        self.image_fences[image_i as usize] = fence;
+        self.previous_frame = frame;
+        self.current_frame = (frame + 1) % self.frame_resources.len();
         return recreate_swapchain;
     }
+    /// Cursor position in pixels from the top-left corner, passed to shaders as `cursor`.
+    pub fn set_cursor_position(&mut self, position: [f32; 2]) {
+        self.cursor_position = position;
+    }
+    /// Moves the whole scene on screen, in pixels from the top-left corner of the window. It is
+    /// passed to shaders as `camera` instead of being recorded, so changing it every frame
+    /// doesn't record prebaked command buffers again.
+    pub fn set_camera(&mut self, camera: Transform) {
+        self.camera = camera;
+    }
+    pub fn camera(&self) -> Transform {
+        self.camera
+    }
+    // The camera applied to vertex coordinates, which are stretched to the window
+    fn camera_matrix(&self) -> Affine {
+        let [width, height] = self.viewport.extent.map(|extent| extent / 2.0);
+        let to_pixels = [[width, 0.0], [0.0, height], [width, height]];
+        let Some(to_vertices) = scene::invert(&to_pixels) else {
+            return IDENTITY_MATRIX;
+        };
+        scene::multiply(
+            &to_vertices,
+            &scene::multiply(&self.camera.to_matrix(), &to_pixels),
+        )
+    }
+    pub fn frames_in_flight(&self) -> usize {
+        self.frame_resources.len()
+    }
+    pub fn latency(&self) -> FrameLatency {
+        self.latency
+    }
+    /// Writes the pipeline cache to disk so the next run skips compiling the same pipelines.
+    pub fn save_pipeline_cache(&self) {
+        save_pipeline_cache(&self.pipeline_cache);
+    }
+    /// Every physical device of the instance, with the reason it was rejected if it can't render.
+    pub fn devices(&self) -> Vec<DeviceCandidate> {
+        list_devices(self.surface.instance(), &self.surface, &device_extensions())
+    }
+    /// Like `devices`, before initializing, to choose `VulkanSettings::device` from the devices
+    /// able to render to `window`.
+    pub fn list_devices(window: &Arc<Window>) -> Vec<DeviceCandidate> {
+        let instance = create_instance(window, false).expect("Failed to create Vulkan instance");
+        let surface = Surface::from_window(instance.clone(), window.clone())
+            .expect("Failed to create Vulkan surface");
+        list_devices(&instance, &surface, &device_extensions())
+    }
     pub fn recreate_swapchain(&mut self, window: &Arc<Window>) {
         let new_dimensions = window.inner_size();
 
@@ -135,10 +458,22 @@ impl Vulkan {
             .swapchain
             .recreate(SwapchainCreateInfo {
                 image_extent: new_dimensions.into(),
+                present_mode: choose_present_mode(
+                    &self.physical_device,
+                    &self.surface,
+                    &self.present_modes,
+                ),
                 ..self.swapchain.create_info()
             })
             .expect("failed to recreate swapchain");
         self.swapchain = new_swapchain;
+        debug!(
+            target: "swapchain",
+            "Recreated at {}x{}, presenting with {:?}",
+            new_dimensions.width,
+            new_dimensions.height,
+            self.swapchain.present_mode()
+        );
 
         let multisampled_image = create_multisampled_image(
             &self.memory_allocator.clone(),
@@ -147,53 +482,604 @@ impl Vulkan {
             self.multisample_state.rasterization_samples,
         );
 
-        let new_framebuffers =
-            get_framebuffers(&new_images, &multisampled_image, &self.render_pass.clone());
+        let stencil_image = create_stencil_image(
+            &self.memory_allocator,
+            window.inner_size().into(),
+            self.stencil_format,
+            self.multisample_state.rasterization_samples,
+        );
 
-        self.viewport.extent = new_dimensions.into();
-        let new_pipeline = get_pipeline(
-            &self.device.clone(),
+        self.framebuffers = get_framebuffers(
+            &new_images,
+            multisampled_image.as_ref(),
+            &stencil_image,
             &self.render_pass.clone(),
-            self.viewport.clone(),
+        );
+        // The globals of an image are only guarded by its fence, which is forgotten here, so
+        // every frame in flight has to finish before they can be written again
+        for frame_resources in &self.frame_resources {
+            if let Some(fence) = &frame_resources.fence {
+                fence.wait(None).unwrap();
+            }
+        }
+        self.image_fences = vec![None; new_images.len()];
+        if new_images.len() != self.global_buffers.len() {
+            (self.global_buffers, self.global_descriptor_sets) = create_global_descriptor_sets(
+                &self.memory_allocator,
+                &self.descriptor_set_allocator,
+                &self.layout,
+                new_images.len(),
+            );
+        }
+        self.scale_factor = window.scale_factor() as f32;
+
+        // Viewport and scissor are dynamic state, so the pipeline survives the resize
+        self.viewport.extent = new_dimensions.into();
+
+        self.rebuild_command_buffers();
+    }
+    /// Switches to `samples`, or the highest sample count below it that the device supports.
+    pub fn set_samples(&mut self, window: &Arc<Window>, samples: SampleCount) {
+        let samples = clamp_sample_count(&self.physical_device, self.stencil_format, samples);
+        if samples == self.multisample_state.rasterization_samples {
+            return;
+        }
+
+        self.render_pass = get_render_pass(
+            self.device.clone(),
+            self.swapchain.clone(),
+            samples,
+            self.stencil_format,
+        );
+        self.multisample_state.rasterization_samples = samples;
+        self.pipeline = get_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.render_pass,
             self.layout.clone(),
             self.stages.clone(),
             &self.vertex_input_state,
             self.multisample_state.clone(),
+            StencilMode::Draw,
+            "shapes pipeline",
+        )
+        .expect("failed to create pipeline");
+        self.clip_pipelines = get_clip_pipelines(
+            &self.device,
+            &self.pipeline_cache,
+            &self.render_pass,
+            &self.layout,
+            &self.stages,
+            &self.vertex_input_state,
+            &self.multisample_state,
+        )
+        .expect("failed to create pipeline");
+        self.material_pipelines.clear();
+        self.materials.clear();
+        self.recreate_swapchain(window);
+    }
+    /// Rebuilds the pipeline when the shader files changed, returns whether it did.
+    /// A shader that fails to compile is reported and the previous pipeline is kept.
+    pub fn poll_shaders(&mut self) -> bool {
+        let Some(result) = self
+            .shader_reloader
+            .as_mut()
+            .and_then(|shader_reloader| shader_reloader.poll(&self.device))
+        else {
+            return false;
+        };
+        match result.and_then(|(vs, fs)| self.reload_shaders(&vs, &fs)) {
+            Ok(()) => {
+                info!(target: "shaders", "Reloaded shaders");
+                true
+            }
+            Err(err) => {
+                error!(target: "shaders", "Keeping the previous pipeline, shader reload failed:\n{err}");
+                false
+            }
+        }
+    }
+    fn reload_shaders(
+        &mut self,
+        vs: &Arc<ShaderModule>,
+        fs: &Arc<ShaderModule>,
+    ) -> Result<(), String> {
+        let (stages, vertex_input_state) = get_stages(vs, fs, self.output_transfer)?;
+        // The layout is kept so that existing descriptor sets stay valid
+        let pipeline = get_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.render_pass,
+            self.layout.clone(),
+            stages.clone(),
+            &vertex_input_state,
+            self.multisample_state.clone(),
+            StencilMode::Draw,
+            "shapes pipeline",
+        )
+        .map_err(|err| err.to_string())?;
+        self.clip_pipelines = get_clip_pipelines(
+            &self.device,
+            &self.pipeline_cache,
+            &self.render_pass,
+            &self.layout,
+            &stages,
+            &vertex_input_state,
+            &self.multisample_state,
+        )
+        .map_err(|err| err.to_string())?;
+        self.pipeline = pipeline;
+        self.stages = stages;
+        self.vertex_input_state = vertex_input_state;
+        self.material_pipelines.clear();
+        self.materials.clear();
+        self.rebuild_command_buffers();
+        Ok(())
+    }
+    pub fn set_present_modes(&mut self, window: &Arc<Window>, present_modes: Vec<PresentMode>) {
+        self.present_modes = present_modes;
+        self.recreate_swapchain(window);
+    }
+    pub fn set_recording_mode(&mut self, recording_mode: RecordingMode) {
+        self.recording_mode = recording_mode;
+        self.rebuild_command_buffers();
+    }
+    /// The element drawn by the node, `None` for groups and removed nodes.
+    pub fn element(&self, id: NodeId) -> Option<&Shape> {
+        let element_i = self.scene.get(id)?.element()?;
+        Some(&self.elements[element_i])
+    }
+    /// Layer and clip changes are drawn from the next frame on. Color changes are too in
+    /// `RecordingMode::PerFrame`, prebaked command buffers keep the colors of the elements
+    /// when they were added.
+    pub fn element_mut(&mut self, id: NodeId) -> Option<&mut Shape> {
+        let element_i = self.scene.get(id)?.element()?;
+        self.scene_changed = true;
+        Some(&mut self.elements[element_i])
+    }
+    /// Nodes placing the elements, initial elements are children of the root in order.
+    pub fn scene(&self) -> &SceneGraph {
+        &self.scene
+    }
+    /// Changes to the transform, visibility and opacity of the node are drawn from the next
+    /// frame on, prebaked command buffers are recorded again before it.
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.scene_changed = true;
+        self.scene.get_mut(id)
+    }
+    /// Moves the node to the end of `parent`'s children, see `SceneGraph::set_parent`.
+    pub fn set_parent(&mut self, id: NodeId, parent: NodeId) -> bool {
+        let moved = self.scene.set_parent(id, parent);
+        if moved {
+            self.rebuild_command_buffers();
+        }
+        moved
+    }
+    /// Adds a node under `parent` drawing `element`, or a group if `element` is `None`.
+    pub fn add_node(&mut self, parent: NodeId, element: Option<Shape>) -> NodeId {
+        let element_i = element.map(|element| {
+            self.push_element(element);
+            self.elements.len() - 1
+        });
+        let id = self.scene.add_node(parent, element_i);
+        self.rebuild_command_buffers();
+        id
+    }
+    /// Removes the node and its descendants, freeing the elements they drew. Later elements
+    /// move down to fill the gaps, like with `Vec::remove`.
+    pub fn remove_node(&mut self, id: NodeId) {
+        let mut removed = self.scene.remove(id);
+        removed.sort_unstable_by(|a, b| b.cmp(a));
+        for element_i in &removed {
+            debug!(target: "scene", "Removing element {element_i}");
+            self.elements.remove(*element_i);
+            for frame_resources in &mut self.frame_resources {
+                frame_resources.color_buffers.remove(*element_i);
+                frame_resources.descriptor_sets.remove(*element_i);
+            }
+        }
+        self.scene.shift_elements(&removed);
+        self.rebuild_command_buffers();
+    }
+    /// The topmost visible node whose element covers `position`, in pixels from the top-left
+    /// corner of the window, taking clips into account.
+    pub fn pick(&self, position: [f32; 2]) -> Option<NodeId> {
+        // Nothing can be picked once the camera has collapsed the scene
+        let inverse_camera = scene::invert(&self.camera_matrix())?;
+        let point = scene::apply(
+            &inverse_camera,
+            [
+                position[0] / self.viewport.extent[0] * 2.0 - 1.0,
+                position[1] / self.viewport.extent[1] * 2.0 - 1.0,
+            ],
+        );
+        draw_order(&self.elements, &self.scene.flatten())
+            .into_iter()
+            .rev()
+            .find(|render_item| {
+                let element = &self.elements[render_item.element];
+                let clip_rect = element
+                    .get_clip_rect()
+                    .and_then(|clip_rect_i| self.clip_rects.get(clip_rect_i));
+                let in_clip_rect = clip_rect.is_none_or(|clip_rect| {
+                    (0..2).all(|axis| {
+                        let offset = clip_rect.offset[axis] as f32;
+                        (offset..offset + clip_rect.extent[axis] as f32).contains(&position[axis])
+                    })
+                });
+                let in_clips =
+                    clip_chain(&self.clips, element.get_clip())
+                        .into_iter()
+                        .all(|clip_i| {
+                            let clip = &self.clips[clip_i];
+                            scene::invert(&clip.transform).is_some_and(|inverse| {
+                                shape_contains(&clip.shape, scene::apply(&inverse, point))
+                            })
+                        });
+                let local_point = scene::invert(&render_item.transform)
+                    .map(|inverse| scene::apply(&inverse, point));
+                in_clip_rect
+                    && in_clips
+                    && local_point.is_some_and(|local_point| shape_contains(element, local_point))
+            })
+            .map(|render_item| render_item.node)
+    }
+    /// Color in sRGB the frame is cleared to, see `VulkanSettings::clear_color`.
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.clear_color = clear_color;
+        self.rebuild_command_buffers();
+    }
+    /// Moves the node relative to its parent. Prebaked command buffers are recorded again once
+    /// before the next frame however often it is called, see `set_camera` to move everything.
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        if let Some(node) = self.scene.get_mut(id) {
+            node.transform = transform;
+            self.scene_changed = true;
+        }
+    }
+    /// Moves the element of the node to `layer`, drawing it over every element on a lower layer.
+    pub fn set_layer(&mut self, id: NodeId, layer: i32) {
+        let Some(element_i) = self.scene.get(id).and_then(|node| node.element()) else {
+            warn!(target: "scene", "{id:?} has no element to move to layer {layer}");
+            return;
+        };
+        self.elements[element_i].set_layer(layer);
+        self.rebuild_command_buffers();
+    }
+    /// Clips the elements added until the matching `pop_clip` to the area of `mask`, which is
+    /// itself clipped by the clips pushed before it. The mask is placed by `node` like the
+    /// node's children, it stays where it was last placed if the node is removed. Returns the
+    /// index of the new clip.
+    pub fn push_clip(&mut self, node: NodeId, mut mask: Shape) -> usize {
+        let clip_i = self.clips.len();
+        debug!(target: "scene", "Pushing clip {clip_i}");
+        upload_geometry(&mut self.uploader, &mut mask, &format!("clip {clip_i}"));
+        self.uploader.flush();
+
+        // Masks only write the stencil buffer, the color is bound to satisfy the pipeline layout
+        let (_, descriptor_set) = create_color_descriptor_set(
+            &self.memory_allocator,
+            &self.descriptor_set_allocator,
+            &self.pipeline,
+            [0.0; 4],
+            &format!("clip {clip_i} color"),
         );
+        mask.update_descriptor_set(descriptor_set);
 
-        self.command_buffers = get_command_buffers(
+        self.clips.push(ClipMask {
+            shape: mask,
+            parent: self.clip_stack.last().copied(),
+            node,
+            transform: self.scene.world_transform(node).unwrap_or(IDENTITY_MATRIX),
+        });
+        self.clip_stack.push(clip_i);
+        clip_i
+    }
+    /// Ends the clip started by the last `push_clip`.
+    pub fn pop_clip(&mut self) {
+        if self.clip_stack.pop().is_none() {
+            warn!(target: "scene", "pop_clip called without a matching push_clip");
+        }
+    }
+    /// Adds a rectangle in window pixels that the elements assigned to it with
+    /// `Shape::set_clip_rect` or `set_element_clip_rect` are cut off at. Returns the index of
+    /// the new clip rect.
+    pub fn add_clip_rect(&mut self, offset: [u32; 2], extent: [u32; 2]) -> usize {
+        self.clip_rects.push(Scissor { offset, extent });
+        self.clip_rects.len() - 1
+    }
+    /// Moves or resizes a clip rect, e.g. when the panel it belongs to changes.
+    pub fn set_clip_rect(&mut self, clip_rect_i: usize, offset: [u32; 2], extent: [u32; 2]) {
+        self.clip_rects[clip_rect_i] = Scissor { offset, extent };
+        self.rebuild_command_buffers();
+    }
+    /// Cuts the element of the node off at a clip rect from `add_clip_rect`, or stops clipping
+    /// it with `None`.
+    pub fn set_element_clip_rect(&mut self, id: NodeId, clip_rect: Option<usize>) {
+        let Some(element_i) = self.scene.get(id).and_then(|node| node.element()) else {
+            warn!(target: "scene", "{id:?} has no element to clip");
+            return;
+        };
+        if clip_rect.is_some_and(|clip_rect_i| clip_rect_i >= self.clip_rects.len()) {
+            warn!(target: "scene", "Ignoring unknown clip rect {clip_rect:?} for {id:?}");
+            return;
+        }
+        self.elements[element_i].set_clip_rect(clip_rect);
+        self.rebuild_command_buffers();
+    }
+    /// Adds the element as the last child of the scene root.
+    pub fn add_element(&mut self, element: Shape) -> NodeId {
+        self.add_node(self.scene.root(), Some(element))
+    }
+    // Elements without a clip are clipped by the innermost clip pushed and not yet popped
+    fn push_element(&mut self, mut element: Shape) {
+        let element_i = self.elements.len();
+        if element.get_clip().is_none() {
+            element.set_clip(self.clip_stack.last().copied());
+        }
+        if let Some(clip_rect_i) = element.get_clip_rect()
+            && clip_rect_i >= self.clip_rects.len()
+        {
+            warn!(target: "scene", "Element {element_i} has unknown clip rect {clip_rect_i}, not clipping it");
+            element.set_clip_rect(None);
+        }
+        debug!(target: "scene", "Adding element {element_i}");
+        upload_geometry(
+            &mut self.uploader,
+            &mut element,
+            &format!("element {element_i}"),
+        );
+        self.uploader.flush();
+
+        let (_, descriptor_set) = create_color_descriptor_set(
+            &self.memory_allocator,
+            &self.descriptor_set_allocator,
+            &self.pipeline,
+            element.get_color(),
+            &format!("element {element_i} color"),
+        );
+        element.update_descriptor_set(descriptor_set);
+
+        for (frame, frame_resources) in self.frame_resources.iter_mut().enumerate() {
+            let (color_buffer, descriptor_set) = create_color_descriptor_set(
+                &self.memory_allocator,
+                &self.descriptor_set_allocator,
+                &self.pipeline,
+                element.get_color(),
+                &format!("frame {frame} element {element_i} color"),
+            );
+            frame_resources.color_buffers.push(color_buffer);
+            frame_resources.descriptor_sets.push(descriptor_set);
+        }
+
+        self.elements.push(element);
+    }
+    fn rebuild_command_buffers(&mut self) {
+        self.prepare_materials();
+        self.update_clip_transforms();
+        self.command_buffers = match self.recording_mode {
+            RecordingMode::Prebaked => get_command_buffers(
+                &self.command_buffer_allocator,
+                &self.queue,
+                &self.pipeline,
+                &self.clip_pipelines,
+                &self.materials,
+                &self.framebuffers,
+                &self.global_descriptor_sets,
+                &self.viewport,
+                self.elements.clone(),
+                &self.scene.flatten(),
+                &self.clips,
+                &self.clip_rects,
+                self.clear_value(),
+            ),
+            RecordingMode::PerFrame => Vec::new(),
+        };
+    }
+    // The fence of `frame` has already been waited on, so its resources are free to write
+    fn record_frame(&mut self, frame: usize, image_i: usize) -> Arc<PrimaryAutoCommandBuffer> {
+        self.prepare_materials();
+        self.update_clip_transforms();
+        let frame_resources = &self.frame_resources[frame];
+        for (element, color_buffer) in self.elements.iter().zip(&frame_resources.color_buffers) {
+            *color_buffer.write().unwrap() = ColorUniform {
+                input_color: element.get_color(),
+            };
+        }
+
+        record_command_buffer(
             &self.command_buffer_allocator,
             &self.queue,
-            &new_pipeline,
-            &new_framebuffers,
-            self.elements.clone(),
-            &self.memory_allocator,
+            &self.pipeline,
+            &self.clip_pipelines,
+            &self.materials,
+            &self.framebuffers[image_i],
+            &self.global_descriptor_sets[image_i],
+            &self.viewport,
+            &self.elements,
+            &self.scene.flatten(),
+            &frame_resources.descriptor_sets,
+            &self.clips,
+            &self.clip_rects,
+            self.clear_value(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+    }
+    // The clear color as written to the swapchain images, premultiplied like everything blended
+    // on top of it unless the compositor multiplies by alpha itself
+    fn clear_value(&self) -> [f32; 4] {
+        let [r, g, b, a] = self.output_transfer.encode(self.clear_color);
+        match self.swapchain.composite_alpha() {
+            CompositeAlpha::PostMultiplied => [r, g, b, a],
+            _ => [r * a, g * a, b * a, a],
+        }
+    }
+    fn update_clip_transforms(&mut self) {
+        for clip in &mut self.clips {
+            if let Some(transform) = self.scene.world_transform(clip.node) {
+                clip.transform = transform;
+            }
+        }
+    }
+    // Builds the pipelines and parameters of the materials referenced by elements for the first
+    // time, and drops those no element references anymore
+    fn prepare_materials(&mut self) {
+        let used: HashMap<MaterialKey, Arc<Material>> = self
+            .elements
+            .iter()
+            .filter_map(Shape::get_material)
+            .map(|material| (MaterialKey::new(&material), material))
+            .collect();
+        self.materials.retain(|key, _| used.contains_key(key));
+        self.material_pipelines
+            .retain(|shader, _| used.keys().any(|key| key.fragment_shader == *shader));
+
+        for (key, material) in used {
+            if self.materials.contains_key(&key) {
+                continue;
+            }
+            let pipeline = match self.material_pipelines.get(&key.fragment_shader) {
+                Some(pipeline) => pipeline.clone(),
+                None => {
+                    let pipeline = match self.build_material_pipeline(&material) {
+                        Ok(pipeline) => {
+                            debug!(target: "scene", "Built pipeline for material {}", material.name);
+                            Some(pipeline)
+                        }
+                        Err(err) => {
+                            error!(
+                                target: "scene",
+                                "Drawing material {} with the default shader, its pipeline failed to build: {err}",
+                                material.name
+                            );
+                            None
+                        }
+                    };
+                    self.material_pipelines
+                        .insert(key.fragment_shader, pipeline.clone());
+                    pipeline
+                }
+            };
+            let material_pipeline = pipeline.and_then(|pipeline| {
+                match self.build_material_params(&material) {
+                    Ok(params) => Some(MaterialPipeline { pipeline, params }),
+                    Err(err) => {
+                        error!(
+                            target: "scene",
+                            "Drawing material {} with the default shader, its parameters failed to upload: {err}",
+                            material.name
+                        );
+                        None
+                    }
+                }
+            });
+            self.materials.insert(key, material_pipeline);
+        }
+    }
+    fn build_material_pipeline(
+        &self,
+        material: &Material,
+    ) -> Result<Arc<GraphicsPipeline>, String> {
+        let fs = (material.fragment_shader)(self.device.clone()).map_err(|err| err.to_string())?;
+        let fs = get_fragment_entry_point(&fs, self.output_transfer)?;
+        get_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.render_pass,
+            self.material_layout.clone(),
+            [
+                self.stages[0].clone(),
+                PipelineShaderStageCreateInfo::new(fs),
+            ],
+            &self.vertex_input_state,
+            self.multisample_state.clone(),
+            StencilMode::Draw,
+            &format!("material {} pipeline", material.name),
+        )
+        .map_err(|err| err.to_string())
+    }
+    fn build_material_params(
+        &self,
+        material: &Material,
+    ) -> Result<Option<Arc<DescriptorSet>>, String> {
+        if material.params.is_empty() {
+            return Ok(None);
+        }
+        let params_buffer = Buffer::from_iter(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::UNIFORM_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            material.params.iter().copied(),
+        )
+        .map_err(|err| err.to_string())?;
+        set_object_name(
+            &self.device,
+            params_buffer.buffer(),
+            &format!("material {} params", material.name),
         );
+        let params = DescriptorSet::new(
+            self.descriptor_set_allocator.clone(),
+            self.material_layout.set_layouts()[2].clone(),
+            [WriteDescriptorSet::buffer(0, params_buffer)],
+            [],
+        )
+        .map_err(|err| err.to_string())?;
+        Ok(Some(params))
     }
     pub fn initialize(
         window: &Arc<Window>,
         mut elements: Vec<Shape>,
-        allow_tearing: bool,
-        samples: SampleCount,
+        settings: &VulkanSettings,
     ) -> Self {
-        let instance = create_instance(window).expect("Failed to create Vulkan instance");
+        let instance =
+            create_instance(window, settings.debug).expect("Failed to create Vulkan instance");
+        let debug_messenger = if settings.debug {
+            create_debug_messenger(&instance, settings.debug_severity)
+        } else {
+            None
+        };
         let surface = Surface::from_window(instance.clone(), window.clone())
             .expect("Failed to create Vulkan surface");
-        let device_extensions = DeviceExtensions {
-            khr_swapchain: true,
-            ..DeviceExtensions::empty()
-        };
+        let device_extensions = device_extensions();
 
+        let device_selector = DeviceSelector::from_env().unwrap_or_else(|| settings.device.clone());
         let (physical_device, queue_family_index) =
-            select_physical_device(&instance, &surface, &device_extensions);
+            select_physical_device(&instance, &surface, &device_extensions, &device_selector);
+        info!(
+            target: "device",
+            "Using {} ({:?})",
+            physical_device.properties().device_name,
+            physical_device.properties().device_type
+        );
+
+        let transfer_queue_family_index = select_transfer_queue_family(&physical_device);
+        debug!(
+            target: "device",
+            "Graphics queue family {queue_family_index}, transfer queue family {transfer_queue_family_index:?}"
+        );
+
+        let mut queue_create_infos = vec![QueueCreateInfo {
+            queue_family_index,
+            ..Default::default()
+        }];
+        if let Some(transfer_queue_family_index) = transfer_queue_family_index {
+            queue_create_infos.push(QueueCreateInfo {
+                queue_family_index: transfer_queue_family_index,
+                ..Default::default()
+            });
+        }
 
         let (device, mut queues) = Device::new(
             physical_device.clone(),
             DeviceCreateInfo {
-                queue_create_infos: vec![QueueCreateInfo {
-                    queue_family_index,
-                    ..Default::default()
-                }],
+                queue_create_infos,
                 enabled_extensions: device_extensions, // new
                 ..Default::default()
             },
@@ -201,11 +1087,35 @@ impl Vulkan {
         .expect("failed to create device");
 
         let queue = queues.next().unwrap();
+        // Fall back to the graphics queue when there is no dedicated transfer queue family
+        let transfer_queue = queues.next().unwrap_or_else(|| queue.clone());
 
-        let (swapchain, images) =
-            create_swapchain(&physical_device, &surface, &window, &device, allow_tearing);
+        let (swapchain, images) = create_swapchain(
+            &physical_device,
+            &surface,
+            &window,
+            &device,
+            &settings.present_modes,
+            settings.format_policy,
+            settings.transparent,
+        );
+        info!(
+            target: "swapchain",
+            "Created {} images of {:?} in {:?}, presenting with {:?} and {:?} composite alpha",
+            images.len(),
+            swapchain.image_format(),
+            swapchain.image_color_space(),
+            swapchain.present_mode(),
+            swapchain.composite_alpha()
+        );
 
-        let render_pass = get_render_pass(device.clone(), swapchain.clone(), samples);
+        let stencil_format = get_stencil_format(&physical_device);
+        let samples = clamp_sample_count(&physical_device, stencil_format, settings.samples);
+        if samples != settings.samples {
+            info!(target: "device", "{:?} is not supported, using {samples:?}", settings.samples);
+        }
+        let render_pass =
+            get_render_pass(device.clone(), swapchain.clone(), samples, stencil_format);
 
         let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
         let multisampled_image = create_multisampled_image(
@@ -214,7 +1124,18 @@ impl Vulkan {
             swapchain.image_format(),
             samples,
         );
-        let framebuffers = get_framebuffers(&images, &multisampled_image, &render_pass.clone());
+        let stencil_image = create_stencil_image(
+            &memory_allocator,
+            window.inner_size().into(),
+            stencil_format,
+            samples,
+        );
+        let framebuffers = get_framebuffers(
+            &images,
+            multisampled_image.as_ref(),
+            &stencil_image,
+            &render_pass.clone(),
+        );
 
         let multisample_state = MultisampleState {
             rasterization_samples: samples,
@@ -230,63 +1151,60 @@ impl Vulkan {
             depth_range: 0.0..=1.0,
         };
 
-        let vs = vs.entry_point("main").unwrap();
-        let fs = fs.entry_point("main").unwrap();
+        let output_transfer =
+            OutputTransfer::new(swapchain.image_format(), swapchain.image_color_space());
 
-        let vertex_input_state = SimpleVertex::per_vertex().definition(&vs).unwrap();
+        let (stages, vertex_input_state) = get_stages(&vs, &fs, output_transfer).unwrap();
 
-        let stages = [
-            PipelineShaderStageCreateInfo::new(vs),
-            PipelineShaderStageCreateInfo::new(fs),
-        ];
+        let layout = get_layout(&device);
+        let material_layout = get_material_layout(&device, &layout);
 
-        let layout = get_layout(&device, stages.clone());
+        let shader_reloader = if settings.shader_hot_reload {
+            let shader_reloader = ShaderReloader::new();
+            if shader_reloader.is_none() {
+                error!(target: "shaders", "Failed to initialize the shader compiler, hot reload is disabled");
+            }
+            shader_reloader
+        } else {
+            None
+        };
 
+        let pipeline_cache = load_pipeline_cache(&device);
         let pipeline = get_pipeline(
             &device.clone(),
+            &pipeline_cache,
             &render_pass.clone(),
-            viewport.clone(),
             layout.clone(),
             stages.clone(),
             &vertex_input_state,
             multisample_state.clone(),
-        );
+            StencilMode::Draw,
+            "shapes pipeline",
+        )
+        .expect("failed to create pipeline");
+        let clip_pipelines = get_clip_pipelines(
+            &device,
+            &pipeline_cache,
+            &render_pass,
+            &layout,
+            &stages,
+            &vertex_input_state,
+            &multisample_state,
+        )
+        .expect("failed to create pipeline");
 
-        for element in elements.iter_mut() {
-            let color_buffer = Buffer::from_data(
-                memory_allocator.clone(),
-                BufferCreateInfo {
-                    usage: BufferUsage::UNIFORM_BUFFER,
-                    ..Default::default()
-                },
-                AllocationCreateInfo {
-                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                    ..Default::default()
-                },
-                ColorUniform {
-                    input_color: element.get_color(),
-                },
-            )
-            .unwrap();
-            let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
-                device.clone(),
-                Default::default(),
-            ));
-            let pipeline_layout = pipeline.layout();
-
-            let descriptor_set_layouts = pipeline_layout.set_layouts();
-            let descriptor_set_layout_index = 0;
-            let descriptor_set_layout = descriptor_set_layouts
-                .get(descriptor_set_layout_index)
-                .unwrap();
-            let descriptor_set = DescriptorSet::new(
-                descriptor_set_allocator,
-                descriptor_set_layout.clone(),
-                [WriteDescriptorSet::buffer(0, color_buffer)],
-                [],
-            )
-            .unwrap();
+        let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
+            device.clone(),
+            Default::default(),
+        ));
+        for (element_i, element) in elements.iter_mut().enumerate() {
+            let (_, descriptor_set) = create_color_descriptor_set(
+                &memory_allocator,
+                &descriptor_set_allocator,
+                &pipeline,
+                element.get_color(),
+                &format!("element {element_i} color"),
+            );
             element.update_descriptor_set(descriptor_set);
         }
 
@@ -295,169 +1213,757 @@ impl Vulkan {
             Default::default(),
         ));
 
-        let command_buffers = get_command_buffers(
+        let mut uploader = Uploader::new(
+            &memory_allocator,
             &command_buffer_allocator,
+            &transfer_queue,
             &queue,
-            &pipeline,
-            &framebuffers,
-            elements.clone(),
+        );
+        for (element_i, element) in elements.iter_mut().enumerate() {
+            upload_geometry(&mut uploader, element, &format!("element {element_i}"));
+        }
+        uploader.flush();
+        debug!(target: "scene", "Uploaded {} elements", elements.len());
+
+        let mut scene = SceneGraph::new();
+        for element_i in 0..elements.len() {
+            scene.add_node(scene.root(), Some(element_i));
+        }
+
+        let frame_resources = (0..settings.frames_in_flight.clamp(1, MAX_FRAMES_IN_FLIGHT))
+            .map(|frame| {
+                create_frame_resources(
+                    &memory_allocator,
+                    &descriptor_set_allocator,
+                    &pipeline,
+                    &elements,
+                    frame,
+                )
+            })
+            .collect();
+
+        let (global_buffers, global_descriptor_sets) = create_global_descriptor_sets(
             &memory_allocator,
+            &descriptor_set_allocator,
+            &layout,
+            images.len(),
         );
-        let frames_in_flight = images.len();
-        Vulkan {
+
+        let mut vulkan = Vulkan {
             swapchain,
             render_pass,
             viewport,
+            physical_device,
+            surface,
             device,
-            command_buffers,
+            present_modes: settings.present_modes.clone(),
+            framebuffers,
+            command_buffers: Vec::new(),
             queue,
+            uploader,
             elements,
-            fences: vec![None; frames_in_flight],
-            previous_fence: 0,
+            scene,
+            scene_changed: false,
+            image_fences: vec![None; images.len()],
+            current_frame: 0,
+            previous_frame: 0,
+            latency: FrameLatency::default(),
             memory_allocator,
             command_buffer_allocator,
+            descriptor_set_allocator,
+            pipeline,
+            clip_pipelines,
+            pipeline_cache,
+            shader_reloader,
             stages,
             vertex_input_state,
             layout,
+            material_layout,
+            material_pipelines: HashMap::new(),
+            materials: HashMap::new(),
             multisample_state,
-        }
+            stencil_format,
+            clips: Vec::new(),
+            clip_stack: Vec::new(),
+            clip_rects: Vec::new(),
+            output_transfer,
+            clear_color: settings.clear_color,
+            recording_mode: settings.recording_mode,
+            frame_resources,
+            global_buffers,
+            global_descriptor_sets,
+            started_at: Instant::now(),
+            frame_count: 0,
+            cursor_position: [0.0, 0.0],
+            scale_factor: window.scale_factor() as f32,
+            camera: Transform::IDENTITY,
+            _debug_messenger: debug_messenger,
+        };
+        vulkan.rebuild_command_buffers();
+        vulkan
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn get_command_buffers(
     command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
     queue: &Arc<Queue>,
     pipeline: &Arc<GraphicsPipeline>,
+    clip_pipelines: &ClipPipelines,
+    materials: &HashMap<MaterialKey, Option<MaterialPipeline>>,
     framebuffers: &Vec<Arc<Framebuffer>>,
-    mut elements: Vec<Shape>,
-    memory_allocator: &Arc<StandardMemoryAllocator>,
+    global_descriptor_sets: &[Arc<DescriptorSet>],
+    viewport: &Viewport,
+    elements: Vec<Shape>,
+    render_list: &[RenderItem],
+    clips: &[ClipMask],
+    clip_rects: &[Scissor],
+    clear_color: [f32; 4],
 ) -> Vec<Arc<PrimaryAutoCommandBuffer>> {
+    let descriptor_sets: Vec<Arc<DescriptorSet>> = elements
+        .iter()
+        .map(|element| element.get_descriptor_set().unwrap())
+        .collect();
     framebuffers
         .iter()
-        .map(|framebuffer| {
-            let clear_values_count = framebuffer.attachments().len();
-            let clear_values: Vec<Option<ClearValue>> =
-                vec![Some([0.1, 0.1, 0.1, 1.0].into()); clear_values_count];
-            let mut builder = AutoCommandBufferBuilder::primary(
-                command_buffer_allocator.clone(),
-                queue.queue_family_index(),
+        .zip(global_descriptor_sets)
+        .map(|(framebuffer, global_descriptor_set)| {
+            record_command_buffer(
+                command_buffer_allocator,
+                queue,
+                pipeline,
+                clip_pipelines,
+                materials,
+                framebuffer,
+                global_descriptor_set,
+                viewport,
+                &elements,
+                render_list,
+                &descriptor_sets,
+                clips,
+                clip_rects,
+                clear_color,
                 CommandBufferUsage::MultipleSubmit,
             )
-            .unwrap();
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_command_buffer(
+    command_buffer_allocator: &Arc<StandardCommandBufferAllocator>,
+    queue: &Arc<Queue>,
+    pipeline: &Arc<GraphicsPipeline>,
+    clip_pipelines: &ClipPipelines,
+    materials: &HashMap<MaterialKey, Option<MaterialPipeline>>,
+    framebuffer: &Arc<Framebuffer>,
+    global_descriptor_set: &Arc<DescriptorSet>,
+    viewport: &Viewport,
+    elements: &[Shape],
+    render_list: &[RenderItem],
+    descriptor_sets: &[Arc<DescriptorSet>],
+    clips: &[ClipMask],
+    clip_rects: &[Scissor],
+    // Encoded by `Vulkan::clear_value`
+    clear_color: [f32; 4],
+    usage: CommandBufferUsage,
+) -> Arc<PrimaryAutoCommandBuffer> {
+    let scissor = Scissor {
+        offset: [0, 0],
+        extent: [viewport.extent[0] as u32, viewport.extent[1] as u32],
+    };
+    let clear_values: Vec<Option<ClearValue>> = framebuffer
+        .attachments()
+        .iter()
+        .map(|attachment| {
+            let aspects = attachment.format().aspects();
+            Some(if aspects.intersects(ImageAspects::DEPTH) {
+                ClearValue::DepthStencil((1.0, 0))
+            } else if aspects.intersects(ImageAspects::STENCIL) {
+                ClearValue::Stencil(0)
+            } else {
+                clear_color.into()
+            })
+        })
+        .collect();
+    let mut builder = AutoCommandBufferBuilder::primary(
+        command_buffer_allocator.clone(),
+        queue.queue_family_index(),
+        usage,
+    )
+    .unwrap();
+
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values,
+                ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+            },
+            SubpassBeginInfo {
+                contents: SubpassContents::Inline,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .bind_pipeline_graphics(pipeline.clone())
+        .unwrap()
+        .set_viewport(0, [viewport.clone()].into_iter().collect())
+        .unwrap()
+        .set_scissor(0, [scissor].into_iter().collect())
+        .unwrap()
+        .set_stencil_reference(StencilFaces::FrontAndBack, 0)
+        .unwrap();
+    let mut bound_pipeline = pipeline;
+    let mut active_scissor = scissor;
+    let mut stencil_reference = 0;
+    // Clips whose masks are in the stencil buffer, outermost first
+    let mut active_clips: Vec<usize> = Vec::new();
+    for render_item in draw_order(elements, render_list) {
+        let element_i = render_item.element;
+        let element = &elements[element_i];
+        let descriptor_set = &descriptor_sets[element_i];
+        let material_pipeline = element
+            .get_material()
+            .and_then(|material| materials.get(&MaterialKey::new(&material))?.as_ref());
+        let (element_pipeline, descriptor_sets) = match material_pipeline {
+            Some(material_pipeline) => (
+                &material_pipeline.pipeline,
+                [
+                    Some(global_descriptor_set.clone()),
+                    Some(descriptor_set.clone()),
+                    material_pipeline.params.clone(),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            ),
+            None => (
+                pipeline,
+                vec![global_descriptor_set.clone(), descriptor_set.clone()],
+            ),
+        };
+
+        // Pop the masks the element is not inside of, then push its own. The stencil value
+        // inside the active masks is their count, a mask only changes it where it is inside
+        // all the masks pushed before it.
+        let element_clips = clip_chain(clips, element.get_clip());
+        let shared = active_clips
+            .iter()
+            .zip(&element_clips)
+            .take_while(|(active_clip, element_clip)| active_clip == element_clip)
+            .count();
+        let mut draws = Vec::new();
+        while active_clips.len() > shared {
+            let clip_i = active_clips.pop().unwrap();
+            draws.push((&clip_pipelines.pop, active_clips.len() + 1, clip_i));
+        }
+        for clip_i in &element_clips[shared..] {
+            draws.push((&clip_pipelines.push, active_clips.len(), *clip_i));
+            active_clips.push(*clip_i);
+        }
+        let draws = draws
+            .into_iter()
+            .map(|(mask_pipeline, reference, clip_i)| {
+                let mask = &clips[clip_i].shape;
+                let mask_descriptor_sets = vec![
+                    global_descriptor_set.clone(),
+                    mask.get_descriptor_set().unwrap(),
+                ];
+                // Masks ignore clip rects so that pushing and popping them cover the same area
+                (
+                    mask_pipeline,
+                    reference,
+                    scissor,
+                    mask_descriptor_sets,
+                    NodeConstants {
+                        transform: clips[clip_i].transform,
+                        opacity: 1.0,
+                    },
+                    mask,
+                )
+            })
+            .chain([(
+                element_pipeline,
+                active_clips.len(),
+                element_scissor(&scissor, clip_rects, element),
+                descriptor_sets,
+                NodeConstants {
+                    transform: render_item.transform,
+                    opacity: render_item.opacity,
+                },
+                element,
+            )]);
 
-            unsafe {
+        for (draw_pipeline, reference, draw_scissor, descriptor_sets, constants, shape) in draws {
+            // Only switch pipelines between shapes with different materials
+            if !Arc::ptr_eq(draw_pipeline, bound_pipeline) {
                 builder
-                    .begin_render_pass(
-                        RenderPassBeginInfo {
-                            clear_values,
-                            ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
-                        },
-                        SubpassBeginInfo {
-                            contents: SubpassContents::Inline,
-                            ..Default::default()
-                        },
-                    )
-                    .unwrap()
-                    .bind_pipeline_graphics(pipeline.clone())
+                    .bind_pipeline_graphics(draw_pipeline.clone())
                     .unwrap();
-                for element in elements.iter_mut() {
-                    match element.get_vertex_buffer() {
-                        Some(_) => {}
-                        None => {
-                            let vertex_buffer = Buffer::from_iter(
-                                memory_allocator.clone(),
-                                BufferCreateInfo {
-                                    usage: BufferUsage::VERTEX_BUFFER,
-                                    ..Default::default()
-                                },
-                                AllocationCreateInfo {
-                                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                                    ..Default::default()
-                                },
-                                element.get_vertices().clone(),
-                            )
-                            .unwrap();
-                            element.update_vertex_buffer(vertex_buffer);
-                        }
-                    }
-
-                    builder
-                        .bind_descriptor_sets(
-                            PipelineBindPoint::Graphics,
-                            pipeline.layout().clone(),
-                            0,
-                            element.get_descriptor_set().clone().unwrap(),
-                        )
-                        .unwrap()
-                        .bind_vertex_buffers(0, element.get_vertex_buffer().clone().unwrap())
-                        .unwrap()
-                        .draw(
-                            element.get_vertex_buffer().clone().unwrap().len() as u32,
-                            1,
-                            0,
-                            0,
-                        )
-                        .unwrap();
-                }
-                builder.end_render_pass(SubpassEndInfo::default()).unwrap();
+                bound_pipeline = draw_pipeline;
+            }
+            if reference as u32 != stencil_reference {
+                builder
+                    .set_stencil_reference(StencilFaces::FrontAndBack, reference as u32)
+                    .unwrap();
+                stencil_reference = reference as u32;
+            }
+            if draw_scissor != active_scissor {
+                builder
+                    .set_scissor(0, [draw_scissor].into_iter().collect())
+                    .unwrap();
+                active_scissor = draw_scissor;
             }
+            draw_shape(
+                &mut builder,
+                draw_pipeline,
+                descriptor_sets,
+                constants,
+                shape,
+            );
+        }
+    }
+    builder.end_render_pass(SubpassEndInfo::default()).unwrap();
+
+    builder.build().unwrap()
+}
+
+fn draw_shape(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pipeline: &Arc<GraphicsPipeline>,
+    descriptor_sets: Vec<Arc<DescriptorSet>>,
+    constants: NodeConstants,
+    shape: &Shape,
+) {
+    let index_buffer = shape.get_index_buffer().unwrap();
+    builder
+        .push_constants(pipeline.layout().clone(), 0, constants)
+        .unwrap()
+        .bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            descriptor_sets,
+        )
+        .unwrap()
+        .bind_vertex_buffers(0, shape.get_vertex_buffer().unwrap())
+        .unwrap()
+        .bind_index_buffer(index_buffer.clone())
+        .unwrap();
+    unsafe { builder.draw_indexed(index_buffer.len() as u32, 1, 0, 0, 0) }.unwrap();
+}
+
+// Clip rect of the element cut down to the framebuffer, which is the whole framebuffer when it has none
+fn element_scissor(
+    framebuffer_scissor: &Scissor,
+    clip_rects: &[Scissor],
+    element: &Shape,
+) -> Scissor {
+    // Unknown clip rects were reported when they were assigned
+    let Some(clip_rect) = element
+        .get_clip_rect()
+        .and_then(|clip_rect_i| clip_rects.get(clip_rect_i))
+    else {
+        return *framebuffer_scissor;
+    };
+    let min = [0, 1].map(|axis| clip_rect.offset[axis].min(framebuffer_scissor.extent[axis]));
+    let max = [0, 1].map(|axis| {
+        clip_rect.offset[axis]
+            .saturating_add(clip_rect.extent[axis])
+            .min(framebuffer_scissor.extent[axis])
+    });
+    Scissor {
+        offset: min,
+        extent: [max[0] - min[0], max[1] - min[1]],
+    }
+}
+
+// Clips enclosing `clip` including itself, outermost first
+fn clip_chain(clips: &[ClipMask], clip: Option<usize>) -> Vec<usize> {
+    // Unknown clips, e.g. set through `Vulkan::element_mut`, don't clip
+    let clip = clip.filter(|clip_i| *clip_i < clips.len());
+    let mut chain: Vec<usize> = iter::successors(clip, |clip_i| clips[*clip_i].parent).collect();
+    chain.reverse();
+    chain
+}
+
+// Whether the point, in the coordinates of the vertices, is inside one of the shape's triangles
+fn shape_contains(shape: &Shape, point: [f32; 2]) -> bool {
+    let vertices = shape.get_vertices();
+    shape.get_indices().chunks_exact(3).any(|triangle| {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position);
+        let edge = |p: [f32; 2], q: [f32; 2]| {
+            (q[0] - p[0]) * (point[1] - p[1]) - (q[1] - p[1]) * (point[0] - p[0])
+        };
+        let sides = [edge(a, b), edge(b, c), edge(c, a)];
+        // Inside whichever way the triangle winds
+        sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+    })
+}
+
+// The render list sorted by layer, the sort is stable so scene order breaks ties
+fn draw_order(elements: &[Shape], render_list: &[RenderItem]) -> Vec<RenderItem> {
+    let mut order = render_list.to_vec();
+    order.sort_by_key(|render_item| elements[render_item.element].get_layer());
+    order
+}
+
+fn create_color_descriptor_set(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    descriptor_set_allocator: &Arc<StandardDescriptorSetAllocator>,
+    pipeline: &Arc<GraphicsPipeline>,
+    color: [f32; 4],
+    name: &str,
+) -> (Subbuffer<ColorUniform>, Arc<DescriptorSet>) {
+    let color_buffer = Buffer::from_data(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::UNIFORM_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        ColorUniform { input_color: color },
+    )
+    .unwrap();
+    set_object_name(color_buffer.device(), color_buffer.buffer(), name);
+    let pipeline_layout = pipeline.layout();
+
+    let descriptor_set_layouts = pipeline_layout.set_layouts();
+    let descriptor_set_layout_index = 1;
+    let descriptor_set_layout = descriptor_set_layouts
+        .get(descriptor_set_layout_index)
+        .unwrap();
+    let descriptor_set = DescriptorSet::new(
+        descriptor_set_allocator.clone(),
+        descriptor_set_layout.clone(),
+        [WriteDescriptorSet::buffer(0, color_buffer.clone())],
+        [],
+    )
+    .unwrap();
+    (color_buffer, descriptor_set)
+}
+
+// One set per swapchain image, rewritten once the image's previous frame has completed
+fn create_global_descriptor_sets(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    descriptor_set_allocator: &Arc<StandardDescriptorSetAllocator>,
+    layout: &Arc<PipelineLayout>,
+    image_count: usize,
+) -> (Vec<Subbuffer<GlobalUniform>>, Vec<Arc<DescriptorSet>>) {
+    (0..image_count)
+        .map(|image_i| {
+            let global_buffer = Buffer::from_data(
+                memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::UNIFORM_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+                GlobalUniform::default(),
+            )
+            .unwrap();
+            set_object_name(
+                global_buffer.device(),
+                global_buffer.buffer(),
+                &format!("image {image_i} globals"),
+            );
+            let descriptor_set = DescriptorSet::new(
+                descriptor_set_allocator.clone(),
+                layout.set_layouts()[0].clone(),
+                [WriteDescriptorSet::buffer(0, global_buffer.clone())],
+                [],
+            )
+            .unwrap();
+            (global_buffer, descriptor_set)
+        })
+        .unzip()
+}
 
-            builder.build().unwrap()
+fn create_frame_resources(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    descriptor_set_allocator: &Arc<StandardDescriptorSetAllocator>,
+    pipeline: &Arc<GraphicsPipeline>,
+    elements: &[Shape],
+    frame: usize,
+) -> FrameResources {
+    let (color_buffers, descriptor_sets) = elements
+        .iter()
+        .enumerate()
+        .map(|(element_i, element)| {
+            create_color_descriptor_set(
+                memory_allocator,
+                descriptor_set_allocator,
+                pipeline,
+                element.get_color(),
+                &format!("frame {frame} element {element_i} color"),
+            )
         })
-        .collect()
+        .unzip();
+    FrameResources {
+        fence: None,
+        submitted_at: None,
+        color_buffers,
+        descriptor_sets,
+    }
 }
 
-fn get_layout(
-    device: &Arc<Device>,
-    stages: [PipelineShaderStageCreateInfo; 2],
-) -> Arc<PipelineLayout> {
+fn upload_geometry(uploader: &mut Uploader, element: &mut Shape, name: &str) {
+    let vertex_buffer = uploader.upload_iter(BufferUsage::VERTEX_BUFFER, element.get_vertices());
+    set_object_name(
+        vertex_buffer.device(),
+        vertex_buffer.buffer(),
+        &format!("{name} vertices"),
+    );
+    element.update_vertex_buffer(vertex_buffer);
+    let index_buffer = create_index_buffer(
+        uploader,
+        element.get_indices(),
+        element.get_vertices().len(),
+    );
+    let index_bytes = index_buffer.as_bytes();
+    set_object_name(
+        index_bytes.device(),
+        index_bytes.buffer(),
+        &format!("{name} indices"),
+    );
+    element.update_index_buffer(index_buffer);
+}
+
+fn create_index_buffer(
+    uploader: &mut Uploader,
+    indices: Vec<u32>,
+    vertex_count: usize,
+) -> IndexBuffer {
+    // 16-bit indices are enough as long as every vertex can be addressed with them
+    if vertex_count <= u16::MAX as usize {
+        uploader
+            .upload_iter(
+                BufferUsage::INDEX_BUFFER,
+                indices.into_iter().map(|index| index as u16),
+            )
+            .into()
+    } else {
+        uploader
+            .upload_iter(BufferUsage::INDEX_BUFFER, indices)
+            .into()
+    }
+}
+
+// Set 0 holds the globals of every shader, set 1 the color of the shape being drawn
+fn get_layout(device: &Arc<Device>) -> Arc<PipelineLayout> {
     PipelineLayout::new(
         device.clone(),
-        PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
-            .into_pipeline_layout_create_info(device.clone())
-            .unwrap(),
+        PipelineLayoutCreateInfo {
+            set_layouts: vec![
+                get_uniform_set_layout(device, ShaderStages::VERTEX | ShaderStages::FRAGMENT),
+                get_uniform_set_layout(device, ShaderStages::FRAGMENT),
+            ],
+            push_constant_ranges: vec![PushConstantRange {
+                stages: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                offset: 0,
+                size: size_of::<NodeConstants>() as u32,
+            }],
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+// A set with a single uniform buffer at binding 0
+fn get_uniform_set_layout(device: &Arc<Device>, stages: ShaderStages) -> Arc<DescriptorSetLayout> {
+    DescriptorSetLayout::new(
+        device.clone(),
+        DescriptorSetLayoutCreateInfo {
+            bindings: [(
+                0,
+                DescriptorSetLayoutBinding {
+                    stages,
+                    ..DescriptorSetLayoutBinding::descriptor_type(DescriptorType::UniformBuffer)
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        },
     )
     .unwrap()
 }
 
+#[allow(clippy::too_many_arguments)]
 fn get_pipeline(
     device: &Arc<Device>,
+    pipeline_cache: &Arc<PipelineCache>,
     render_pass: &Arc<RenderPass>,
-    viewport: Viewport,
     layout: Arc<PipelineLayout>,
     stages: [PipelineShaderStageCreateInfo; 2],
     vertex_input_state: &VertexInputState,
     multisample_state: MultisampleState,
-) -> Arc<GraphicsPipeline> {
+    stencil_mode: StencilMode,
+    name: &str,
+) -> Result<Arc<GraphicsPipeline>, Validated<VulkanError>> {
     let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
 
-    GraphicsPipeline::new(
+    let (pass_op, color_write_mask, blend) = match stencil_mode {
+        StencilMode::Draw => (
+            StencilOp::Keep,
+            ColorComponents::all(),
+            // Colors are weighted by their alpha, which premultiplies them over a premultiplied
+            // clear and keeps them straight over a straight one. Alpha is accumulated with `One`
+            // so that it is the coverage the compositor expects either way.
+            Some(AttachmentBlend {
+                src_alpha_blend_factor: BlendFactor::One,
+                ..AttachmentBlend::alpha()
+            }),
+        ),
+        StencilMode::PushClip => (StencilOp::IncrementAndClamp, ColorComponents::empty(), None),
+        StencilMode::PopClip => (StencilOp::DecrementAndClamp, ColorComponents::empty(), None),
+    };
+    // Fragments pass where the stencil value equals the dynamic reference, the number of
+    // clip masks that are active when drawing
+    let stencil_op_state = StencilOpState {
+        ops: StencilOps {
+            pass_op,
+            compare_op: CompareOp::Equal,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let pipeline = GraphicsPipeline::new(
         device.clone(),
-        None,
+        Some(pipeline_cache.clone()),
         GraphicsPipelineCreateInfo {
             stages: stages.into_iter().collect(),
             vertex_input_state: Some(vertex_input_state.clone()),
             input_assembly_state: Some(InputAssemblyState::default()),
-            viewport_state: Some(ViewportState {
-                viewports: [viewport].into_iter().collect(),
-                ..Default::default()
-            }),
+            viewport_state: Some(ViewportState::default()),
             rasterization_state: Some(RasterizationState::default()),
             multisample_state: Some(multisample_state),
+            depth_stencil_state: Some(DepthStencilState {
+                stencil: Some(StencilState {
+                    front: stencil_op_state,
+                    back: stencil_op_state,
+                }),
+                ..Default::default()
+            }),
             color_blend_state: Some(ColorBlendState::with_attachment_states(
                 subpass.num_color_attachments(),
-                ColorBlendAttachmentState::default(),
+                ColorBlendAttachmentState {
+                    blend,
+                    color_write_mask,
+                    ..Default::default()
+                },
             )),
+            dynamic_state: [
+                DynamicState::Viewport,
+                DynamicState::Scissor,
+                DynamicState::StencilReference,
+            ]
+            .into_iter()
+            .collect(),
             subpass: Some(subpass.into()),
             ..GraphicsPipelineCreateInfo::layout(layout)
         },
+    )?;
+    set_object_name(device, &pipeline, name);
+    Ok(pipeline)
+}
+
+fn get_clip_pipelines(
+    device: &Arc<Device>,
+    pipeline_cache: &Arc<PipelineCache>,
+    render_pass: &Arc<RenderPass>,
+    layout: &Arc<PipelineLayout>,
+    stages: &[PipelineShaderStageCreateInfo; 2],
+    vertex_input_state: &VertexInputState,
+    multisample_state: &MultisampleState,
+) -> Result<ClipPipelines, Validated<VulkanError>> {
+    let get_clip_pipeline = |stencil_mode, name| {
+        get_pipeline(
+            device,
+            pipeline_cache,
+            render_pass,
+            layout.clone(),
+            stages.clone(),
+            vertex_input_state,
+            multisample_state.clone(),
+            stencil_mode,
+            name,
+        )
+    };
+    Ok(ClipPipelines {
+        push: get_clip_pipeline(StencilMode::PushClip, "push clip pipeline")?,
+        pop: get_clip_pipeline(StencilMode::PopClip, "pop clip pipeline")?,
+    })
+}
+
+fn get_stages(
+    vs: &Arc<ShaderModule>,
+    fs: &Arc<ShaderModule>,
+    output_transfer: OutputTransfer,
+) -> Result<([PipelineShaderStageCreateInfo; 2], VertexInputState), String> {
+    let vs = vs
+        .entry_point("main")
+        .ok_or("vertex shader has no main entry point")?;
+    let fs = get_fragment_entry_point(fs, output_transfer)?;
+
+    let vertex_input_state = SimpleVertex::per_vertex()
+        .definition(&vs)
+        .map_err(|err| err.to_string())?;
+
+    Ok((
+        [
+            PipelineShaderStageCreateInfo::new(vs),
+            PipelineShaderStageCreateInfo::new(fs),
+        ],
+        vertex_input_state,
+    ))
+}
+// Material shaders may leave out the `OUTPUT_TRANSFER` specialization constant
+fn get_fragment_entry_point(
+    fs: &Arc<ShaderModule>,
+    output_transfer: OutputTransfer,
+) -> Result<EntryPoint, String> {
+    let specialization_info = if fs.specialization_constants().contains_key(&0) {
+        [(0, SpecializationConstant::U32(output_transfer as u32))]
+            .into_iter()
+            .collect()
+    } else {
+        HashMap::default()
+    };
+    fs.specialize(specialization_info)
+        .map_err(|err| err.to_string())?
+        .entry_point("main")
+        .ok_or("fragment shader has no main entry point".to_owned())
+}
+
+// Same sets as `layout`, followed by the set holding the material parameters
+fn get_material_layout(device: &Arc<Device>, layout: &Arc<PipelineLayout>) -> Arc<PipelineLayout> {
+    let mut set_layouts = layout.set_layouts().to_vec();
+    set_layouts.push(get_uniform_set_layout(device, ShaderStages::FRAGMENT));
+    PipelineLayout::new(
+        device.clone(),
+        PipelineLayoutCreateInfo {
+            set_layouts,
+            push_constant_ranges: layout.push_constant_ranges().to_vec(),
+            ..Default::default()
+        },
     )
     .unwrap()
 }
+
 fn get_framebuffers(
     images: &[Arc<Image>],
-    multisampled_image: &Arc<ImageView>,
+    multisampled_image: Option<&Arc<ImageView>>,
+    stencil_image: &Arc<ImageView>,
     render_pass: &Arc<RenderPass>,
 ) -> Vec<Arc<Framebuffer>> {
     images
@@ -467,7 +1973,12 @@ fn get_framebuffers(
             Framebuffer::new(
                 render_pass.clone(),
                 FramebufferCreateInfo {
-                    attachments: vec![multisampled_image.clone(), view],
+                    attachments: match multisampled_image {
+                        Some(multisampled_image) => {
+                            vec![multisampled_image.clone(), view, stencil_image.clone()]
+                        }
+                        None => vec![view, stencil_image.clone()],
+                    },
                     ..Default::default()
                 },
             )
@@ -480,7 +1991,34 @@ fn get_render_pass(
     device: Arc<Device>,
     swapchain: Arc<Swapchain>,
     samples: SampleCount,
+    stencil_format: Format,
 ) -> Arc<RenderPass> {
+    // Without multisampling there is nothing to resolve, render straight to the swapchain
+    if samples == SampleCount::Sample1 {
+        return vulkano::single_pass_renderpass!(
+            device,
+            attachments: {
+                color: {
+                    format: swapchain.image_format(),
+                    samples: SampleCount::Sample1,
+                    load_op: Clear,
+                    store_op: Store,
+                },
+                stencil: {
+                    format: stencil_format,
+                    samples: SampleCount::Sample1,
+                    load_op: Clear,
+                    store_op: DontCare,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {stencil},
+            },
+        )
+        .unwrap();
+    }
+
     vulkano::single_pass_renderpass!(
         device,
         attachments: {
@@ -496,63 +2034,135 @@ fn get_render_pass(
                 load_op: Clear,
                 store_op: Store,
             },
+            stencil: {
+                format: stencil_format,
+                samples: samples,
+                load_op: Clear,
+                store_op: DontCare,
+            },
         },
         pass: {
             color: [multisample],
             color_resolve: [color],
-            depth_stencil: {},
+            depth_stencil: {stencil},
         },
     )
     .unwrap()
 }
-fn select_physical_device(
-    instance: &Arc<Instance>,
-    surface: &Arc<Surface>,
-    device_extensions: &DeviceExtensions,
-) -> (Arc<PhysicalDevice>, u32) {
-    instance
-        .enumerate_physical_devices()
-        .expect("could not enumerate devices")
-        .filter(|p| p.supported_extensions().contains(&device_extensions))
-        .filter_map(|p| {
-            p.queue_family_properties()
-                .iter()
-                .enumerate()
-                .position(|(i, q)| {
-                    q.queue_flags.contains(QueueFlags::GRAPHICS)
-                        && p.surface_support(i as u32, &surface).unwrap_or(false)
-                })
-                .map(|q| (p, q as u32))
-        })
-        .min_by_key(|(p, _)| match p.properties().device_type {
-            PhysicalDeviceType::DiscreteGpu => 0,
-            PhysicalDeviceType::IntegratedGpu => 1,
-            PhysicalDeviceType::VirtualGpu => 2,
-            PhysicalDeviceType::Cpu => 3,
-            _ => 4,
+// Clip masks only need a stencil aspect, formats with depth are the fallback
+fn get_stencil_format(physical_device: &Arc<PhysicalDevice>) -> Format {
+    [
+        Format::S8_UINT,
+        Format::D24_UNORM_S8_UINT,
+        Format::D32_SFLOAT_S8_UINT,
+        Format::D16_UNORM_S8_UINT,
+    ]
+    .into_iter()
+    .find(|format| {
+        physical_device
+            .format_properties(*format)
+            .is_ok_and(|format_properties| {
+                format_properties
+                    .optimal_tiling_features
+                    .intersects(FormatFeatures::DEPTH_STENCIL_ATTACHMENT)
+            })
+    })
+    .expect("no stencil format available")
+}
+// The stencil attachment is multisampled like the color attachments, so both have to support it
+fn clamp_sample_count(
+    physical_device: &Arc<PhysicalDevice>,
+    stencil_format: Format,
+    samples: SampleCount,
+) -> SampleCount {
+    let properties = physical_device.properties();
+    let mut supported_sample_counts =
+        properties.framebuffer_color_sample_counts & properties.framebuffer_stencil_sample_counts;
+    if stencil_format != Format::S8_UINT {
+        supported_sample_counts &= properties.framebuffer_depth_sample_counts;
+    }
+    [
+        SampleCount::Sample64,
+        SampleCount::Sample32,
+        SampleCount::Sample16,
+        SampleCount::Sample8,
+        SampleCount::Sample4,
+        SampleCount::Sample2,
+    ]
+    .into_iter()
+    .filter(|sample_count| (*sample_count as u32) <= (samples as u32))
+    .find(|sample_count| supported_sample_counts.contains_enum(*sample_count))
+    .unwrap_or(SampleCount::Sample1)
+}
+
+fn select_transfer_queue_family(physical_device: &Arc<PhysicalDevice>) -> Option<u32> {
+    physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .filter(|(_, q)| {
+            q.queue_flags.contains(QueueFlags::TRANSFER)
+                && !q.queue_flags.contains(QueueFlags::GRAPHICS)
         })
-        .expect("no device available")
+        // Prefer a pure transfer (DMA) family over an async compute one
+        .min_by_key(|(_, q)| q.queue_flags.contains(QueueFlags::COMPUTE))
+        .map(|(i, _)| i as u32)
+}
+
+fn device_extensions() -> DeviceExtensions {
+    DeviceExtensions {
+        khr_swapchain: true,
+        ..DeviceExtensions::empty()
+    }
 }
 
-fn create_instance(window: &Arc<Window>) -> Result<Arc<Instance>, Validated<VulkanError>> {
+fn create_instance(
+    window: &Arc<Window>,
+    debug: bool,
+) -> Result<Arc<Instance>, Validated<VulkanError>> {
     let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let enabled_layers = if debug {
+        validation_layers(&library)
+    } else {
+        Vec::new()
+    };
+    let debug_extensions = if debug {
+        debug_extensions(&library, &enabled_layers)
+    } else {
+        InstanceExtensions::empty()
+    };
     let required_extensions = Surface::required_extensions(&(*window)).unwrap();
+    // Needed for surfaces to report HDR color spaces
+    let optional_extensions = InstanceExtensions {
+        ext_swapchain_colorspace: true,
+        ..InstanceExtensions::empty()
+    };
     let instance = Instance::new(
-        library,
+        library.clone(),
         InstanceCreateInfo {
             flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
-            enabled_extensions: required_extensions,
+            enabled_layers,
+            enabled_extensions: required_extensions
+                | optional_extensions.intersection(library.supported_extensions())
+                | debug_extensions,
             ..Default::default()
         },
     );
     instance
 }
 
-fn choose_memory_efficient_format(
-    available_formats: &Vec<(Format, ColorSpace)>,
-) -> vulkano::format::Format {
-    use vulkano::format::Format;
-    let preferred_formats = [
+fn choose_surface_format(
+    available_formats: &[(Format, ColorSpace)],
+    format_policy: FormatPolicy,
+) -> (Format, ColorSpace) {
+    const QUALITY_FORMATS: [Format; 5] = [
+        Format::B8G8R8A8_SRGB,
+        Format::R8G8B8A8_SRGB,
+        Format::A8B8G8R8_SRGB_PACK32,
+        Format::B8G8R8A8_UNORM,
+        Format::R8G8B8A8_UNORM,
+    ];
+    const MEMORY_FORMATS: [Format; 9] = [
         Format::R5G6B5_UNORM_PACK16,   // 16 bits per pixel
         Format::A1R5G5B5_UNORM_PACK16, // 16 bits per pixel
         Format::B4G4R4A4_UNORM_PACK16, // 16 bits per pixel
@@ -563,16 +2173,56 @@ fn choose_memory_efficient_format(
         Format::R8G8B8A8_SRGB,         // 32 bits per pixel
         Format::B8G8R8A8_SRGB,         // 32 bits per pixel
     ];
+    const HDR_FORMATS: [Format; 3] = [
+        Format::R16G16B16A16_SFLOAT,      // 64 bits per pixel
+        Format::A2B10G10R10_UNORM_PACK32, // 32 bits per pixel
+        Format::A2R10G10B10_UNORM_PACK32, // 32 bits per pixel
+    ];
+    const HDR_SURFACE_FORMATS: [(Format, ColorSpace); 3] = [
+        (Format::R16G16B16A16_SFLOAT, ColorSpace::ExtendedSrgbLinear),
+        (Format::A2B10G10R10_UNORM_PACK32, ColorSpace::Hdr10St2084),
+        (Format::A2R10G10B10_UNORM_PACK32, ColorSpace::Hdr10St2084),
+    ];
 
-    for preferred_format in preferred_formats.iter() {
-        if available_formats
+    if format_policy == FormatPolicy::Hdr
+        && let Some(surface_format) = HDR_SURFACE_FORMATS
             .iter()
-            .any(|(format, _)| format == preferred_format)
-        {
-            return *preferred_format;
+            .find(|surface_format| available_formats.contains(surface_format))
+    {
+        return *surface_format;
+    }
+
+    let preferred_formats: Vec<Format> = match format_policy {
+        FormatPolicy::Quality => QUALITY_FORMATS.to_vec(),
+        FormatPolicy::Memory => MEMORY_FORMATS.to_vec(),
+        FormatPolicy::Hdr => [HDR_FORMATS.as_slice(), QUALITY_FORMATS.as_slice()].concat(),
+    };
+
+    for preferred_format in preferred_formats.iter() {
+        if let Some(surface_format) = available_formats.iter().find(|(format, color_space)| {
+            format == preferred_format && *color_space == ColorSpace::SrgbNonLinear
+        }) {
+            return *surface_format;
         }
     }
-    available_formats[0].0
+    available_formats[0]
+}
+
+fn choose_present_mode(
+    physical_device: &Arc<PhysicalDevice>,
+    surface: &Arc<Surface>,
+    preferred_present_modes: &[PresentMode],
+) -> PresentMode {
+    let supported_present_modes = physical_device
+        .surface_present_modes(surface, Default::default())
+        .expect("failed to get surface present modes");
+
+    preferred_present_modes
+        .iter()
+        .copied()
+        .find(|present_mode| supported_present_modes.contains(present_mode))
+        // FIFO is the only present mode every surface is required to support
+        .unwrap_or(PresentMode::Fifo)
 }
 
 fn create_multisampled_image(
@@ -580,7 +2230,11 @@ fn create_multisampled_image(
     extent: [u32; 2],
     format: Format,
     samples: SampleCount,
-) -> Arc<ImageView> {
+) -> Option<Arc<ImageView>> {
+    if samples == SampleCount::Sample1 {
+        return None;
+    }
+
     let image = Image::new(
         allocator.clone(),
         ImageCreateInfo {
@@ -598,6 +2252,32 @@ fn create_multisampled_image(
     )
     .unwrap();
 
+    Some(ImageView::new_default(image).unwrap())
+}
+
+fn create_stencil_image(
+    allocator: &Arc<StandardMemoryAllocator>,
+    extent: [u32; 2],
+    format: Format,
+    samples: SampleCount,
+) -> Arc<ImageView> {
+    let image = Image::new(
+        allocator.clone(),
+        ImageCreateInfo {
+            image_type: ImageType::Dim2d,
+            format,
+            extent: [extent[0], extent[1], 1],
+            usage: ImageUsage::TRANSIENT_ATTACHMENT | ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            samples,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
     ImageView::new_default(image).unwrap()
 }
 
@@ -606,18 +2286,20 @@ fn create_swapchain(
     surface: &Arc<Surface>,
     window: &Arc<Window>,
     device: &Arc<Device>,
-    allow_tearing: bool,
+    present_modes: &[PresentMode],
+    format_policy: FormatPolicy,
+    transparent: bool,
 ) -> (Arc<Swapchain>, Vec<Arc<Image>>) {
     let caps = physical_device
         .surface_capabilities(&surface, Default::default())
         .expect("failed to get surface capabilities");
 
     let dimensions = window.inner_size();
-    let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
+    let composite_alpha = choose_composite_alpha(caps.supported_composite_alpha, transparent);
     let image_formats = physical_device
         .surface_formats(&surface, Default::default())
         .unwrap();
-    let image_format = choose_memory_efficient_format(&image_formats);
+    let (image_format, image_color_space) = choose_surface_format(&image_formats, format_policy);
 
     Swapchain::new(
         device.clone(),
@@ -625,20 +2307,42 @@ fn create_swapchain(
         SwapchainCreateInfo {
             min_image_count: caps.min_image_count,
             image_format,
+            image_color_space,
             image_extent: dimensions.into(),
             image_usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
             composite_alpha,
-            present_mode: if allow_tearing {
-                PresentMode::Immediate
-            } else {
-                PresentMode::Fifo
-            },
+            present_mode: choose_present_mode(physical_device, surface, present_modes),
             ..Default::default()
         },
     )
     .unwrap()
 }
 
+// `Opaque` ignores the alpha of the swapchain images, transparent windows need it composited.
+// With `PostMultiplied` the frame is cleared with straight alpha, translucent shapes over
+// transparent areas then come out darker, as they are blended with the clear color.
+fn choose_composite_alpha(supported: CompositeAlphas, transparent: bool) -> CompositeAlpha {
+    let preferred: &[CompositeAlpha] = if transparent {
+        &[
+            CompositeAlpha::PreMultiplied,
+            CompositeAlpha::PostMultiplied,
+            CompositeAlpha::Inherit,
+        ]
+    } else {
+        &[CompositeAlpha::Opaque]
+    };
+    preferred
+        .iter()
+        .copied()
+        .find(|composite_alpha| supported.contains_enum(*composite_alpha))
+        .unwrap_or_else(|| {
+            if transparent {
+                warn!(target: "swapchain", "The surface can't be composited with alpha, {supported:?} are supported");
+            }
+            supported.into_iter().next().unwrap()
+        })
+}
+
 #[derive(BufferContents, Vertex, Clone, Debug)]
 #[repr(C)]
 pub struct SimpleVertex {
@@ -646,8 +2350,132 @@ pub struct SimpleVertex {
     pub position: [f32; 2],
 }
 
+/// Values refreshed every frame for all shaders, declared in GLSL as
+///
+/// ```glsl
+/// layout(set = 0, binding = 0) uniform Globals {
+///     float time;
+///     uint frame;
+///     vec2 resolution;
+///     vec2 cursor;
+///     float scale_factor;
+///     mat3x2 camera;
+/// };
+/// ```
+#[repr(C)]
+#[derive(Default, BufferContents)]
+struct GlobalUniform {
+    /// Seconds since `Vulkan::initialize`.
+    time: f32,
+    frame: u32,
+    /// Framebuffer size in pixels.
+    resolution: [f32; 2],
+    /// Cursor position in pixels from the top-left corner of the window.
+    cursor: [f32; 2],
+    scale_factor: f32,
+    _padding: f32,
+    /// `Vulkan::camera` in vertex coordinates, std140 pads every column to a `vec4`.
+    camera: [[f32; 4]; 3],
+}
+
+/// Push constants of every draw, declared in the shaders as
+///
+/// ```glsl
+/// layout(push_constant) uniform Node {
+///     mat3x2 transform;
+///     float opacity;
+/// } node;
+/// ```
+#[repr(C)]
+#[derive(Clone, Copy, BufferContents)]
+struct NodeConstants {
+    transform: Affine,
+    opacity: f32,
+}
+
+impl Default for NodeConstants {
+    fn default() -> Self {
+        NodeConstants {
+            transform: Transform::IDENTITY.to_matrix(),
+            opacity: 1.0,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Default, BufferContents)]
 struct ColorUniform {
     input_color: [f32; 4],
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shape_contains_rectangle() {
+        let rectangle = Shape::new_rectangle(0.0, 0.0, 0.5, 0.25, [1.0; 4]);
+        assert!(shape_contains(&rectangle, [0.25, 0.1]));
+        // Edges count as inside
+        assert!(shape_contains(&rectangle, [0.0, 0.0]));
+        assert!(shape_contains(&rectangle, [0.5, 0.25]));
+        assert!(!shape_contains(&rectangle, [0.25, 0.3]));
+        assert!(!shape_contains(&rectangle, [-0.1, 0.1]));
+    }
+
+    #[test]
+    fn shape_contains_either_winding() {
+        let vertices = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        for vertices in [vertices, [vertices[0], vertices[2], vertices[1]]] {
+            let triangle = Shape::new_triangle(
+                vertices
+                    .iter()
+                    .map(|position| SimpleVertex {
+                        position: *position,
+                    })
+                    .collect(),
+                [1.0; 4],
+            );
+            assert!(shape_contains(&triangle, [0.2, 0.2]));
+            assert!(!shape_contains(&triangle, [0.6, 0.6]));
+        }
+    }
+
+    #[test]
+    fn composite_alpha_falls_back_to_post_multiplied() {
+        let supported = CompositeAlphas::OPAQUE | CompositeAlphas::POST_MULTIPLIED;
+        assert_eq!(
+            choose_composite_alpha(supported, true),
+            CompositeAlpha::PostMultiplied
+        );
+        assert_eq!(
+            choose_composite_alpha(supported | CompositeAlphas::PRE_MULTIPLIED, true),
+            CompositeAlpha::PreMultiplied
+        );
+        assert_eq!(
+            choose_composite_alpha(supported, false),
+            CompositeAlpha::Opaque
+        );
+    }
+
+    // Different bodies so the two loaders can't be merged into one address
+    fn unused_shader(_device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        unreachable!("unused_shader is only compared")
+    }
+
+    fn other_shader(_device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        unreachable!("other_shader is only compared")
+    }
+
+    #[test]
+    fn material_key_ignores_name() {
+        let material = Material::new("a", unused_shader, vec![[1.0, 0.5, 0.0, 0.0]]);
+        let renamed = Material::new("b", unused_shader, vec![[1.0, 0.5, 0.0, 0.0]]);
+        assert!(MaterialKey::new(&material) == MaterialKey::new(&renamed));
+
+        let new_params = Material::new("a", unused_shader, vec![[2.0, 0.5, 0.0, 0.0]]);
+        assert!(MaterialKey::new(&material) != MaterialKey::new(&new_params));
+        let new_shader = Material::new("a", other_shader, vec![[1.0, 0.5, 0.0, 0.0]]);
+        assert!(MaterialKey::new(&material) != MaterialKey::new(&new_shader));
+    }
+}
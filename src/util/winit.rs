@@ -1 +1,5 @@
 pub mod app;
+pub mod gamepad;
+pub mod gestures;
+pub mod input;
+pub mod window;
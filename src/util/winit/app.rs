@@ -1,39 +1,167 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
-use vulkano::image::SampleCount;
+use log::{debug, info, trace};
+use vulkano::{image::SampleCount, swapchain::PresentMode};
 use winit::{
     application::ApplicationHandler,
     event::WindowEvent,
-    event_loop::ActiveEventLoop,
-    window::{Window, WindowAttributes, WindowId},
+    event_loop::{ActiveEventLoop, ControlFlow},
+    window::{Fullscreen, Window, WindowId},
 };
 
 use crate::util::{
-    components::shape::Shape,
-    vulkano::vulkano_utils::{SimpleVertex, Vulkan},
+    components::{material::Material, shape::Shape},
+    shaders::{hot_reload::POLL_INTERVAL, shaders::stripes_shader},
+    vulkano::vulkano_utils::{SimpleVertex, Vulkan, VulkanSettings},
+    winit::{
+        gestures::{Gesture, GestureRecognizer},
+        input::Input,
+        window::{WindowMode, WindowSettings, fullscreen},
+    },
 };
 
 #[derive(Default)]
 pub struct App {
     window: Option<Arc<Window>>,
     vulkan: Option<Vulkan>,
+    settings: VulkanSettings,
+    window_settings: WindowSettings,
+    input: Input,
+    gestures: GestureRecognizer,
     size: [u32; 2],
     resized: bool,
     recreate_swapchain: bool,
 }
 
+impl App {
+    /// A transparent window also turns on `VulkanSettings::transparent`.
+    pub fn new(settings: VulkanSettings, window_settings: WindowSettings) -> Self {
+        App {
+            settings: VulkanSettings {
+                transparent: settings.transparent || window_settings.transparent,
+                ..settings
+            },
+            window_settings,
+            input: Input::new(),
+            ..Default::default()
+        }
+    }
+    /// Switches between windowed and fullscreen on the monitor the window is on.
+    pub fn set_window_mode(&mut self, mode: WindowMode) {
+        self.window_settings.mode = mode;
+        if let Some(window) = self.window.as_ref() {
+            info!(target: "window", "Switching to {mode:?}");
+            window.set_fullscreen(fullscreen(mode, window.current_monitor()));
+            // Exclusive fullscreen can change the surface without resizing the window
+            self.recreate_swapchain = true;
+            window.request_redraw();
+        }
+    }
+    /// The mode the window is in, which the platform may have changed since `set_window_mode`,
+    /// e.g. when the window manager leaves fullscreen.
+    pub fn window_mode(&self) -> WindowMode {
+        let Some(window) = self.window.as_ref() else {
+            return self.window_settings.mode;
+        };
+        match window.fullscreen() {
+            None => WindowMode::Windowed,
+            Some(Fullscreen::Borderless(_)) => WindowMode::Borderless,
+            Some(Fullscreen::Exclusive(_)) => WindowMode::Exclusive,
+        }
+    }
+    // Taps and long presses pick shapes for `Input::just_tapped` and `Input::just_long_pressed`,
+    // the other gestures move the camera, once for all the gestures of an event
+    fn handle_gestures(&mut self, gestures: impl IntoIterator<Item = Gesture>) {
+        let (Some(vulkan), Some(window)) = (self.vulkan.as_mut(), self.window.as_ref()) else {
+            return;
+        };
+        let mut camera = vulkan.camera();
+        for gesture in gestures {
+            match gesture {
+                Gesture::Tap { position } => {
+                    let node = vulkan.pick(position);
+                    debug!(target: "input", "Tapped {node:?}");
+                    if let Some(node) = node {
+                        self.input.handle_tap(node);
+                        window.request_redraw();
+                    }
+                }
+                Gesture::LongPress { position } => {
+                    let node = vulkan.pick(position);
+                    debug!(target: "input", "Long pressed {node:?}");
+                    if let Some(node) = node {
+                        self.input.handle_long_press(node);
+                        window.request_redraw();
+                    }
+                }
+                Gesture::Pan { delta, .. } => camera.translate(delta),
+                Gesture::Pinch { center, scale } => camera.scale_about(center, scale),
+                Gesture::Rotate { center, angle } => camera.rotate_about(center, angle),
+            }
+        }
+        if camera != vulkan.camera() {
+            vulkan.set_camera(camera);
+            window.request_redraw();
+        }
+    }
+    // Runs the actions triggered since the last frame
+    fn update(&mut self, event_loop: &ActiveEventLoop) {
+        if self.input.action_just_pressed("quit") {
+            info!(target: "window", "Quit pressed; stopping");
+            event_loop.exit();
+        }
+        for (action, fullscreen_mode) in [
+            ("toggle_fullscreen", WindowMode::Borderless),
+            ("toggle_exclusive_fullscreen", WindowMode::Exclusive),
+        ] {
+            // Goes straight from one fullscreen mode to the other
+            if self.input.action_just_pressed(action) {
+                self.set_window_mode(if self.window_mode() == fullscreen_mode {
+                    WindowMode::Windowed
+                } else {
+                    fullscreen_mode
+                });
+            }
+        }
+        self.input.end_frame();
+    }
+    /// Switches to `samples`, clamped to what the device supports.
+    pub fn set_samples(&mut self, samples: SampleCount) {
+        self.settings.samples = samples;
+        if let (Some(vulkan), Some(window)) = (self.vulkan.as_mut(), self.window.as_ref()) {
+            vulkan.set_samples(window, samples);
+        }
+    }
+    /// Switches to the first supported mode of `present_modes`, recreating the swapchain.
+    pub fn set_present_modes(&mut self, present_modes: Vec<PresentMode>) {
+        self.settings.present_modes = present_modes.clone();
+        if let (Some(vulkan), Some(window)) = (self.vulkan.as_mut(), self.window.as_ref()) {
+            vulkan.set_present_modes(window, present_modes);
+        }
+    }
+}
+
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         match self.vulkan {
             Some(_) => {}
             None => {
-                println!("Initializing Vulkan");
+                info!(target: "device", "Initializing Vulkan");
                 self.window = Some(Arc::new(
                     event_loop
-                        .create_window(WindowAttributes::default())
+                        .create_window(
+                            self.window_settings
+                                .attributes(event_loop.primary_monitor()),
+                        )
                         .unwrap(),
                 ));
                 let window = self.window.clone().unwrap();
+                let mut rectangle = Shape::new_rectangle(0.0, 0.0, 0.5, 0.5, [1.0, 0.0, 1.0, 1.0]);
+                rectangle.set_material(Some(Arc::new(Material::new(
+                    "stripes",
+                    stripes_shader::load,
+                    vec![[16.0, 0.5, 0.0, 0.0]],
+                ))));
                 self.vulkan = Some(Vulkan::initialize(
                     &window,
                     vec![
@@ -65,29 +193,86 @@ impl ApplicationHandler for App {
                             ],
                             [0.0, 1.0, 0.0, 1.0],
                         ),
-                        Shape::new_rectangle(0.0, 0.0, 0.5, 0.5, [1.0, 0.0, 1.0, 1.0]),
+                        rectangle,
                     ],
-                    true,
-                    SampleCount::Sample4,
+                    &self.settings,
                 ));
-                println!("Vulkan initialized");
+                info!(target: "device", "Vulkan initialized");
+            }
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let now = Instant::now();
+        let mut changed = self.input.poll_gamepads();
+        let mut shader_poll = None;
+        if self.settings.shader_hot_reload {
+            if let Some(vulkan) = self.vulkan.as_mut() {
+                changed |= vulkan.poll_shaders();
             }
+            shader_poll = Some(now + POLL_INTERVAL);
+        }
+        let long_press = self.gestures.poll();
+        self.handle_gestures(long_press);
+        if changed && let Some(window) = self.window.as_ref() {
+            window.request_redraw();
+        }
+
+        // Wake up to read gamepads, look for shader changes and trigger long presses
+        let wake_at = [
+            self.input
+                .gamepad_poll_interval()
+                .map(|interval| now + interval),
+            shader_poll,
+            self.gestures.deadline(),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        event_loop.set_control_flow(match wake_at {
+            Some(wake_at) => ControlFlow::WaitUntil(wake_at),
+            None => ControlFlow::Wait,
+        });
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(vulkan) = self.vulkan.as_ref() {
+            vulkan.save_pipeline_cache();
         }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         match event {
             WindowEvent::Resized(size) => {
-                println!("Resized to {}x{}", size.width, size.height);
+                debug!(target: "window", "Resized to {}x{}", size.width, size.height);
                 self.size = [size.width, size.height];
                 self.resized = true;
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some(vulkan) = self.vulkan.as_mut() {
+                    vulkan.set_cursor_position([position.x as f32, position.y as f32]);
+                }
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                self.input.handle_key_event(&event);
+                if let Some(window) = self.window.as_ref() {
+                    window.request_redraw();
+                }
+            }
+            WindowEvent::Touch(touch) => {
+                let gestures = self.gestures.handle_touch(&touch);
+                self.handle_gestures(gestures);
+            }
+            WindowEvent::Focused(false) => {
+                self.input.release_all();
+            }
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                info!(target: "window", "The close button was pressed; stopping");
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
-                println!("Redraw requested");
+                trace!(target: "window", "Redraw requested");
+                self.update(event_loop);
                 if self.resized || self.recreate_swapchain {
                     self.resized = false;
                     match self.vulkan.as_mut() {
@@ -0,0 +1,484 @@
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use std::{
+    fs, io, mem,
+    path::PathBuf,
+    time::{Instant, SystemTime},
+};
+
+#[cfg(target_os = "linux")]
+use evdev::{AbsInfo, AbsoluteAxisCode, Device, EventSummary, KeyCode};
+#[cfg(target_os = "linux")]
+use log::{info, warn};
+
+/// How often connected gamepads are read while at least one is connected.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(8);
+/// How often `/dev/input` is checked for newly connected gamepads.
+pub const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(target_os = "linux")]
+const INPUT_DIR: &str = "/dev/input";
+
+/// Identifies a gamepad until it is disconnected, a reconnected gamepad gets a new ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GamepadId(pub(super) u32);
+
+/// Buttons named by their position, like the `BTN_SOUTH` family of evdev codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 17] = [
+        GamepadButton::South,
+        GamepadButton::East,
+        GamepadButton::North,
+        GamepadButton::West,
+        GamepadButton::LeftBumper,
+        GamepadButton::RightBumper,
+        GamepadButton::LeftTrigger,
+        GamepadButton::RightTrigger,
+        GamepadButton::Select,
+        GamepadButton::Start,
+        GamepadButton::Mode,
+        GamepadButton::LeftThumb,
+        GamepadButton::RightThumb,
+        GamepadButton::DPadUp,
+        GamepadButton::DPadDown,
+        GamepadButton::DPadLeft,
+        GamepadButton::DPadRight,
+    ];
+}
+
+/// Sticks range from -1.0 to 1.0 with y pointing down, triggers from 0.0 to 1.0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+    DPadX,
+    DPadY,
+}
+
+impl GamepadAxis {
+    fn is_trigger(self) -> bool {
+        matches!(self, GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GamepadEvent {
+    Connected {
+        id: GamepadId,
+        name: String,
+    },
+    Disconnected {
+        id: GamepadId,
+    },
+    Button {
+        id: GamepadId,
+        button: GamepadButton,
+        pressed: bool,
+    },
+    /// Axis values are normalized but the deadzone is not applied yet.
+    Axis {
+        id: GamepadId,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+/// Scales `value` so that it is 0.0 inside the deadzone and reaches 1.0 at the same place.
+pub fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    if value.abs() <= deadzone {
+        return 0.0;
+    }
+    value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+}
+
+/// Gamepads read from the Linux evdev interface, picking up devices connected later on,
+/// including virtual ones created through uinput. Other platforms have no gamepads.
+#[derive(Default)]
+pub struct Gamepads {
+    #[cfg(target_os = "linux")]
+    devices: Vec<EvdevGamepad>,
+    #[cfg(target_os = "linux")]
+    next_id: u32,
+    #[cfg(target_os = "linux")]
+    last_scan: Option<Instant>,
+    // Changes when devices are added or removed, the directory is only listed again then
+    #[cfg(target_os = "linux")]
+    input_dir_modified: Option<SystemTime>,
+    // Device nodes that couldn't be opened, retried on every scan as udev may only grant
+    // access after creating them
+    #[cfg(target_os = "linux")]
+    unopened: Vec<PathBuf>,
+}
+
+impl Gamepads {
+    /// How long to wait before calling `poll` again, `None` when gamepads are not supported.
+    pub fn poll_interval(&self) -> Option<Duration> {
+        #[cfg(target_os = "linux")]
+        return Some(if self.devices.is_empty() {
+            SCAN_INTERVAL
+        } else {
+            POLL_INTERVAL
+        });
+        #[cfg(not(target_os = "linux"))]
+        None
+    }
+
+    /// Reads the events of the connected gamepads and looks for new ones every `SCAN_INTERVAL`.
+    #[cfg(target_os = "linux")]
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        if self
+            .last_scan
+            .is_none_or(|last_scan| last_scan.elapsed() >= SCAN_INTERVAL)
+        {
+            self.last_scan = Some(Instant::now());
+            self.scan(&mut events);
+        }
+
+        self.devices
+            .retain_mut(|gamepad| match gamepad.read(&mut events) {
+                Ok(()) => true,
+                Err(err) => {
+                    info!(target: "input", "Gamepad {} disconnected: {err}", gamepad.name);
+                    events.push(GamepadEvent::Disconnected { id: gamepad.id });
+                    false
+                }
+            });
+        events
+    }
+    #[cfg(not(target_os = "linux"))]
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        Vec::new()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn scan(&mut self, events: &mut Vec<GamepadEvent>) {
+        let modified = fs::metadata(INPUT_DIR)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let paths = if modified.is_none() || modified != self.input_dir_modified {
+            self.input_dir_modified = modified;
+            self.unopened.clear();
+            match fs::read_dir(INPUT_DIR) {
+                Ok(entries) => entries
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .filter(|path| {
+                        path.file_name()
+                            .and_then(|name| name.to_str())
+                            .is_some_and(|name| name.starts_with("event"))
+                    })
+                    .collect(),
+                Err(err) => {
+                    warn!(target: "input", "Could not list {INPUT_DIR}: {err}");
+                    Vec::new()
+                }
+            }
+        } else {
+            mem::take(&mut self.unopened)
+        };
+
+        for path in paths {
+            // Tracked devices aren't opened a second time
+            if self.devices.iter().any(|gamepad| gamepad.path == path) {
+                continue;
+            }
+            let Ok(device) = Device::open(&path) else {
+                self.unopened.push(path);
+                continue;
+            };
+            if !is_gamepad(&device) {
+                continue;
+            }
+            if let Err(err) = device.set_nonblocking(true) {
+                warn!(target: "input", "Ignoring gamepad {}: {err}", path.display());
+                continue;
+            }
+
+            let id = GamepadId(self.next_id);
+            self.next_id += 1;
+            let name = device.name().unwrap_or("unnamed gamepad").to_owned();
+            info!(target: "input", "Gamepad {name} connected at {}", path.display());
+            let axes = device
+                .get_absinfo()
+                .map(|axes| axes.collect())
+                .unwrap_or_default();
+            events.push(GamepadEvent::Connected {
+                id,
+                name: name.clone(),
+            });
+            self.devices.push(EvdevGamepad {
+                id,
+                name,
+                path,
+                device,
+                axes,
+            });
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct EvdevGamepad {
+    id: GamepadId,
+    name: String,
+    path: PathBuf,
+    device: Device,
+    // Range of every absolute axis, to normalize their values
+    axes: Vec<(AbsoluteAxisCode, AbsInfo)>,
+}
+
+#[cfg(target_os = "linux")]
+impl EvdevGamepad {
+    // Fails once the device is gone
+    fn read(&mut self, events: &mut Vec<GamepadEvent>) -> io::Result<()> {
+        let input_events = match self.device.fetch_events() {
+            Ok(input_events) => input_events,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        for input_event in input_events {
+            match input_event.destructure() {
+                EventSummary::Key(_, code, value) => {
+                    // Auto repeats have a value of 2
+                    if let Some(button) = button(code)
+                        && value != 2
+                    {
+                        events.push(GamepadEvent::Button {
+                            id: self.id,
+                            button,
+                            pressed: value == 1,
+                        });
+                    }
+                }
+                EventSummary::AbsoluteAxis(_, code, value) => {
+                    let Some(axis) = axis(code) else {
+                        continue;
+                    };
+                    let Some((_, info)) =
+                        self.axes.iter().find(|(axis_code, _)| *axis_code == code)
+                    else {
+                        continue;
+                    };
+                    events.push(GamepadEvent::Axis {
+                        id: self.id,
+                        axis,
+                        value: normalize(axis, value, info),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// Joysticks and other devices with buttons are skipped, gamepads have the face buttons
+#[cfg(target_os = "linux")]
+fn is_gamepad(device: &Device) -> bool {
+    device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(KeyCode::BTN_SOUTH))
+}
+
+#[cfg(target_os = "linux")]
+fn button(code: KeyCode) -> Option<GamepadButton> {
+    Some(match code {
+        KeyCode::BTN_SOUTH => GamepadButton::South,
+        KeyCode::BTN_EAST => GamepadButton::East,
+        KeyCode::BTN_NORTH => GamepadButton::North,
+        KeyCode::BTN_WEST => GamepadButton::West,
+        KeyCode::BTN_TL => GamepadButton::LeftBumper,
+        KeyCode::BTN_TR => GamepadButton::RightBumper,
+        KeyCode::BTN_TL2 => GamepadButton::LeftTrigger,
+        KeyCode::BTN_TR2 => GamepadButton::RightTrigger,
+        KeyCode::BTN_SELECT => GamepadButton::Select,
+        KeyCode::BTN_START => GamepadButton::Start,
+        KeyCode::BTN_MODE => GamepadButton::Mode,
+        KeyCode::BTN_THUMBL => GamepadButton::LeftThumb,
+        KeyCode::BTN_THUMBR => GamepadButton::RightThumb,
+        KeyCode::BTN_DPAD_UP => GamepadButton::DPadUp,
+        KeyCode::BTN_DPAD_DOWN => GamepadButton::DPadDown,
+        KeyCode::BTN_DPAD_LEFT => GamepadButton::DPadLeft,
+        KeyCode::BTN_DPAD_RIGHT => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn axis(code: AbsoluteAxisCode) -> Option<GamepadAxis> {
+    Some(match code {
+        AbsoluteAxisCode::ABS_X => GamepadAxis::LeftX,
+        AbsoluteAxisCode::ABS_Y => GamepadAxis::LeftY,
+        AbsoluteAxisCode::ABS_RX => GamepadAxis::RightX,
+        AbsoluteAxisCode::ABS_RY => GamepadAxis::RightY,
+        AbsoluteAxisCode::ABS_Z => GamepadAxis::LeftTrigger,
+        AbsoluteAxisCode::ABS_RZ => GamepadAxis::RightTrigger,
+        AbsoluteAxisCode::ABS_HAT0X => GamepadAxis::DPadX,
+        AbsoluteAxisCode::ABS_HAT0Y => GamepadAxis::DPadY,
+        _ => return None,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn normalize(axis: GamepadAxis, value: i32, info: &AbsInfo) -> f32 {
+    let range = (info.maximum() - info.minimum()).max(1) as f32;
+    let unit = (value - info.minimum()) as f32 / range;
+    if axis.is_trigger() {
+        unit.clamp(0.0, 1.0)
+    } else {
+        (unit * 2.0 - 1.0).clamp(-1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadzone() {
+        assert_eq!(apply_deadzone(0.1, 0.25), 0.0);
+        assert_eq!(apply_deadzone(-0.25, 0.25), 0.0);
+        assert_eq!(apply_deadzone(0.625, 0.25), 0.5);
+        assert_eq!(apply_deadzone(-0.625, 0.25), -0.5);
+        assert_eq!(apply_deadzone(1.0, 0.25), 1.0);
+        assert_eq!(apply_deadzone(-1.0, 0.25), -1.0);
+        assert_eq!(apply_deadzone(0.5, 0.0), 0.5);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn normalize_axes() {
+        let stick = AbsInfo::new(0, -32768, 32767, 0, 0, 0);
+        assert_eq!(normalize(GamepadAxis::LeftX, -32768, &stick), -1.0);
+        assert_eq!(normalize(GamepadAxis::LeftX, 32767, &stick), 1.0);
+        assert!(normalize(GamepadAxis::LeftX, 0, &stick).abs() < 1e-4);
+
+        let trigger = AbsInfo::new(0, 0, 255, 0, 0, 0);
+        assert_eq!(normalize(GamepadAxis::LeftTrigger, 0, &trigger), 0.0);
+        assert_eq!(normalize(GamepadAxis::LeftTrigger, 255, &trigger), 1.0);
+        // Values outside the reported range are clamped
+        assert_eq!(normalize(GamepadAxis::RightTrigger, 300, &trigger), 1.0);
+
+        let hat = AbsInfo::new(0, -1, 1, 0, 0, 0);
+        assert_eq!(normalize(GamepadAxis::DPadY, -1, &hat), -1.0);
+        assert_eq!(normalize(GamepadAxis::DPadY, 0, &hat), 0.0);
+        assert_eq!(normalize(GamepadAxis::DPadY, 1, &hat), 1.0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn map_codes() {
+        assert_eq!(button(KeyCode::BTN_SOUTH), Some(GamepadButton::South));
+        assert_eq!(button(KeyCode::KEY_A), None);
+        assert_eq!(
+            axis(AbsoluteAxisCode::ABS_RZ),
+            Some(GamepadAxis::RightTrigger)
+        );
+        assert_eq!(axis(AbsoluteAxisCode::ABS_WHEEL), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs write access to /dev/uinput"]
+    fn virtual_gamepad() {
+        use std::thread::sleep;
+
+        use evdev::{AttributeSet, EventType, InputEvent, UinputAbsSetup, uinput::VirtualDevice};
+
+        const NAME: &str = "Gamepads test pad";
+        let mut buttons = AttributeSet::<KeyCode>::new();
+        buttons.insert(KeyCode::BTN_SOUTH);
+        let stick = AbsInfo::new(0, -32768, 32767, 0, 0, 0);
+        let mut device = VirtualDevice::builder()
+            .unwrap()
+            .name(NAME)
+            .with_keys(&buttons)
+            .unwrap()
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisCode::ABS_X, stick))
+            .unwrap()
+            .build()
+            .unwrap();
+        device
+            .enumerate_dev_nodes_blocking()
+            .unwrap()
+            .for_each(drop);
+
+        // Polls until `find` matches an event, giving udev time to set up the device node
+        let mut gamepads = Gamepads::default();
+        let mut poll_until = |find: &dyn Fn(&GamepadEvent) -> bool| {
+            let started_at = Instant::now();
+            while started_at.elapsed() < SCAN_INTERVAL * 5 {
+                if let Some(event) = gamepads.poll().into_iter().find(|event| find(event)) {
+                    return event;
+                }
+                sleep(POLL_INTERVAL);
+            }
+            panic!("no matching gamepad event");
+        };
+
+        let GamepadEvent::Connected { id, .. } = poll_until(
+            &|event| matches!(event, GamepadEvent::Connected { name, .. } if name == NAME),
+        ) else {
+            unreachable!();
+        };
+        device
+            .emit(&[
+                InputEvent::new(EventType::KEY.0, KeyCode::BTN_SOUTH.code(), 1),
+                InputEvent::new(EventType::ABSOLUTE.0, AbsoluteAxisCode::ABS_X.0, 32767),
+            ])
+            .unwrap();
+        poll_until(&|event| {
+            *event
+                == GamepadEvent::Button {
+                    id,
+                    button: GamepadButton::South,
+                    pressed: true,
+                }
+        });
+        // The first axis event may have been read in the same poll as the button
+        device
+            .emit(&[InputEvent::new(
+                EventType::ABSOLUTE.0,
+                AbsoluteAxisCode::ABS_X.0,
+                -32768,
+            )])
+            .unwrap();
+        poll_until(&|event| {
+            *event
+                == GamepadEvent::Axis {
+                    id,
+                    axis: GamepadAxis::LeftX,
+                    value: -1.0,
+                }
+        });
+
+        drop(device);
+        poll_until(&|event| *event == GamepadEvent::Disconnected { id });
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use winit::event::{Touch, TouchPhase};
+
+/// How long a finger has to rest to trigger a long press instead of a tap.
+pub const LONG_PRESS_DURATION: Duration = Duration::from_millis(500);
+/// How far in pixels a finger can move before a tap or long press turns into a pan.
+pub const TOUCH_SLOP: f32 = 16.0;
+
+/// Positions are in pixels from the top-left corner of the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    Tap {
+        position: [f32; 2],
+    },
+    LongPress {
+        position: [f32; 2],
+    },
+    /// One finger dragging, or the center of several fingers moving together.
+    Pan {
+        position: [f32; 2],
+        delta: [f32; 2],
+    },
+    /// Two fingers moving apart when `scale` is above 1.0, relative to the last pinch.
+    Pinch {
+        center: [f32; 2],
+        scale: f32,
+    },
+    /// Two fingers turning clockwise on screen when `angle` is positive, in radians since the
+    /// last rotate.
+    Rotate {
+        center: [f32; 2],
+        angle: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Idle,
+    // One finger down that hasn't moved, a tap or long press
+    Pressing {
+        started_at: Instant,
+        start: [f32; 2],
+    },
+    Panning,
+    LongPressed,
+    // Stays until every finger is lifted so lifting one of two doesn't start a pan
+    MultiTouch,
+}
+
+/// Turns the touch events of the window into gestures.
+#[derive(Debug)]
+pub struct GestureRecognizer {
+    touches: HashMap<u64, [f32; 2]>,
+    state: State,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        GestureRecognizer {
+            touches: HashMap::new(),
+            state: State::Idle,
+        }
+    }
+}
+
+impl GestureRecognizer {
+    pub fn handle_touch(&mut self, touch: &Touch) -> Vec<Gesture> {
+        let position = [touch.location.x as f32, touch.location.y as f32];
+        match touch.phase {
+            TouchPhase::Started => {
+                self.touches.insert(touch.id, position);
+                self.state = match self.touches.len() {
+                    1 => State::Pressing {
+                        started_at: Instant::now(),
+                        start: position,
+                    },
+                    _ => State::MultiTouch,
+                };
+                Vec::new()
+            }
+            TouchPhase::Moved => self.moved(touch.id, position),
+            TouchPhase::Ended => {
+                self.touches.remove(&touch.id);
+                let mut gestures = Vec::new();
+                if let State::Pressing { started_at, start } = self.state {
+                    // The long press may not have been polled before the finger was lifted
+                    gestures.push(if started_at.elapsed() < LONG_PRESS_DURATION {
+                        Gesture::Tap { position: start }
+                    } else {
+                        Gesture::LongPress { position: start }
+                    });
+                }
+                if self.touches.is_empty() {
+                    self.state = State::Idle;
+                }
+                gestures
+            }
+            TouchPhase::Cancelled => {
+                self.touches.remove(&touch.id);
+                if self.touches.is_empty() {
+                    self.state = State::Idle;
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    /// Triggers the long press once its finger has rested long enough.
+    pub fn poll(&mut self) -> Option<Gesture> {
+        let State::Pressing { started_at, start } = self.state else {
+            return None;
+        };
+        if started_at.elapsed() < LONG_PRESS_DURATION {
+            return None;
+        }
+        self.state = State::LongPressed;
+        Some(Gesture::LongPress { position: start })
+    }
+
+    /// When `poll` should be called next, if a long press is pending.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.state {
+            State::Pressing { started_at, .. } => Some(started_at + LONG_PRESS_DURATION),
+            _ => None,
+        }
+    }
+
+    fn moved(&mut self, id: u64, position: [f32; 2]) -> Vec<Gesture> {
+        // A finger that went down before the window had focus never sent `Started`
+        if !self.touches.contains_key(&id) {
+            return Vec::new();
+        }
+        // Positions of the first two fingers before the move
+        let mut ids: Vec<u64> = self.touches.keys().copied().collect();
+        ids.sort();
+        let pair = |touches: &HashMap<u64, [f32; 2]>| {
+            (ids.len() >= 2).then(|| (touches[&ids[0]], touches[&ids[1]]))
+        };
+        let before = pair(&self.touches);
+        let previous = self.touches.insert(id, position).unwrap();
+
+        let mut gestures = match self.state {
+            State::Pressing { start, .. } if distance(start, position) > TOUCH_SLOP => {
+                self.state = State::Panning;
+                vec![Gesture::Pan {
+                    position,
+                    delta: sub(position, start),
+                }]
+            }
+            State::Panning => vec![Gesture::Pan {
+                position,
+                delta: sub(position, previous),
+            }],
+            State::MultiTouch => {
+                let (Some((a0, b0)), Some((a1, b1))) = (before, pair(&self.touches)) else {
+                    return Vec::new();
+                };
+                let center0 = midpoint(a0, b0);
+                let center1 = midpoint(a1, b1);
+                let angle0 = (b0[1] - a0[1]).atan2(b0[0] - a0[0]);
+                let angle1 = (b1[1] - a1[1]).atan2(b1[0] - a1[0]);
+                let mut angle = angle1 - angle0;
+                // Keep the shortest way around when crossing the atan2 discontinuity
+                if angle > std::f32::consts::PI {
+                    angle -= std::f32::consts::TAU;
+                } else if angle < -std::f32::consts::PI {
+                    angle += std::f32::consts::TAU;
+                }
+                vec![
+                    Gesture::Pan {
+                        position: center1,
+                        delta: sub(center1, center0),
+                    },
+                    Gesture::Pinch {
+                        center: center1,
+                        scale: distance(a1, b1) / distance(a0, b0).max(1.0),
+                    },
+                    Gesture::Rotate {
+                        center: center1,
+                        angle,
+                    },
+                ]
+            }
+            _ => Vec::new(),
+        };
+        // Fingers often move one at a time, leaving parts of a multi-touch gesture unchanged
+        gestures.retain(|gesture| !gesture.is_identity());
+        gestures
+    }
+}
+
+impl Gesture {
+    // A gesture that wouldn't move anything
+    fn is_identity(&self) -> bool {
+        match *self {
+            Gesture::Pan { delta, .. } => delta == [0.0, 0.0],
+            Gesture::Pinch { scale, .. } => scale == 1.0,
+            Gesture::Rotate { angle, .. } => angle == 0.0,
+            Gesture::Tap { .. } | Gesture::LongPress { .. } => false,
+        }
+    }
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn midpoint(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0]
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let [x, y] = sub(a, b);
+    x.hypot(y)
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::{dpi::PhysicalPosition, event::DeviceId};
+
+    use super::*;
+
+    fn touch(id: u64, phase: TouchPhase, position: [f64; 2]) -> Touch {
+        Touch {
+            device_id: DeviceId::dummy(),
+            phase,
+            location: PhysicalPosition::new(position[0], position[1]),
+            force: None,
+            id,
+        }
+    }
+
+    // Pretends the finger went down long enough ago for a long press
+    fn backdate_press(recognizer: &mut GestureRecognizer) {
+        let State::Pressing { start, .. } = recognizer.state else {
+            panic!("no press pending");
+        };
+        recognizer.state = State::Pressing {
+            started_at: Instant::now().checked_sub(LONG_PRESS_DURATION).unwrap(),
+            start,
+        };
+    }
+
+    #[test]
+    fn tap() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [10.0, 20.0]));
+        assert!(recognizer.deadline().is_some());
+        // Moving within the slop doesn't start a pan
+        let moved = recognizer.handle_touch(&touch(0, TouchPhase::Moved, [15.0, 20.0]));
+        assert!(moved.is_empty());
+        assert_eq!(
+            recognizer.handle_touch(&touch(0, TouchPhase::Ended, [15.0, 20.0])),
+            vec![Gesture::Tap {
+                position: [10.0, 20.0]
+            }]
+        );
+        assert_eq!(recognizer.deadline(), None);
+    }
+
+    #[test]
+    fn long_press_polled() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [10.0, 20.0]));
+        assert_eq!(recognizer.poll(), None);
+        backdate_press(&mut recognizer);
+        assert_eq!(
+            recognizer.poll(),
+            Some(Gesture::LongPress {
+                position: [10.0, 20.0]
+            })
+        );
+        assert_eq!(recognizer.poll(), None);
+        // Lifting the finger after the long press doesn't tap
+        assert!(
+            recognizer
+                .handle_touch(&touch(0, TouchPhase::Ended, [10.0, 20.0]))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn long_press_released_before_poll() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [10.0, 20.0]));
+        backdate_press(&mut recognizer);
+        assert_eq!(
+            recognizer.handle_touch(&touch(0, TouchPhase::Ended, [10.0, 20.0])),
+            vec![Gesture::LongPress {
+                position: [10.0, 20.0]
+            }]
+        );
+    }
+
+    #[test]
+    fn pan() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [0.0, 0.0]));
+        assert_eq!(
+            recognizer.handle_touch(&touch(0, TouchPhase::Moved, [20.0, 0.0])),
+            vec![Gesture::Pan {
+                position: [20.0, 0.0],
+                delta: [20.0, 0.0]
+            }]
+        );
+        assert_eq!(
+            recognizer.handle_touch(&touch(0, TouchPhase::Moved, [25.0, 5.0])),
+            vec![Gesture::Pan {
+                position: [25.0, 5.0],
+                delta: [5.0, 5.0]
+            }]
+        );
+        assert_eq!(recognizer.deadline(), None);
+        assert!(
+            recognizer
+                .handle_touch(&touch(0, TouchPhase::Ended, [25.0, 5.0]))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn cancelled_press_does_not_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [0.0, 0.0]));
+        recognizer.handle_touch(&touch(0, TouchPhase::Cancelled, [0.0, 0.0]));
+        assert_eq!(recognizer.state, State::Idle);
+        assert_eq!(recognizer.poll(), None);
+    }
+
+    #[test]
+    fn pinch_and_rotate() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [0.0, 0.0]));
+        recognizer.handle_touch(&touch(1, TouchPhase::Started, [10.0, 0.0]));
+        assert_eq!(recognizer.deadline(), None);
+        // The second finger moves to twice the distance, a quarter turn clockwise on screen
+        let gestures = recognizer.handle_touch(&touch(1, TouchPhase::Moved, [0.0, 20.0]));
+        assert_eq!(
+            gestures,
+            vec![
+                Gesture::Pan {
+                    position: [0.0, 10.0],
+                    delta: [-5.0, 10.0]
+                },
+                Gesture::Pinch {
+                    center: [0.0, 10.0],
+                    scale: 2.0
+                },
+                Gesture::Rotate {
+                    center: [0.0, 10.0],
+                    angle: std::f32::consts::FRAC_PI_2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rotate_across_atan2_discontinuity() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [0.0, 0.0]));
+        // Just above the negative x axis, at an angle close to -PI
+        recognizer.handle_touch(&touch(1, TouchPhase::Started, [-10.0, -0.1]));
+        // Just below it, close to PI
+        let gestures = recognizer.handle_touch(&touch(1, TouchPhase::Moved, [-10.0, 0.1]));
+        let Some(Gesture::Rotate { angle, .. }) = gestures.last() else {
+            panic!("no rotate in {gestures:?}");
+        };
+        assert!(angle.abs() < 0.1, "turned the long way around: {angle}");
+        assert!(*angle < 0.0);
+    }
+
+    #[test]
+    fn identity_gestures_are_skipped() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [0.0, 0.0]));
+        recognizer.handle_touch(&touch(1, TouchPhase::Started, [10.0, 0.0]));
+        // Sliding along the line between the fingers pans and pinches without turning
+        assert_eq!(
+            recognizer.handle_touch(&touch(1, TouchPhase::Moved, [20.0, 0.0])),
+            vec![
+                Gesture::Pan {
+                    position: [10.0, 0.0],
+                    delta: [5.0, 0.0]
+                },
+                Gesture::Pinch {
+                    center: [10.0, 0.0],
+                    scale: 2.0
+                },
+            ]
+        );
+        // Reporting the same position again moves nothing
+        assert!(
+            recognizer
+                .handle_touch(&touch(1, TouchPhase::Moved, [20.0, 0.0]))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn unknown_touch_is_ignored() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [0.0, 0.0]));
+        assert!(
+            recognizer
+                .handle_touch(&touch(1, TouchPhase::Moved, [50.0, 0.0]))
+                .is_empty()
+        );
+        assert!(!recognizer.touches.contains_key(&1));
+        assert_eq!(
+            recognizer.handle_touch(&touch(0, TouchPhase::Ended, [0.0, 0.0])),
+            vec![Gesture::Tap {
+                position: [0.0, 0.0]
+            }]
+        );
+    }
+
+    #[test]
+    fn lifting_one_of_two_fingers_does_not_pan() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.handle_touch(&touch(0, TouchPhase::Started, [0.0, 0.0]));
+        recognizer.handle_touch(&touch(1, TouchPhase::Started, [10.0, 0.0]));
+        assert!(
+            recognizer
+                .handle_touch(&touch(1, TouchPhase::Ended, [10.0, 0.0]))
+                .is_empty()
+        );
+        assert!(
+            recognizer
+                .handle_touch(&touch(0, TouchPhase::Moved, [50.0, 0.0]))
+                .is_empty()
+        );
+        recognizer.handle_touch(&touch(0, TouchPhase::Ended, [50.0, 0.0]));
+        assert_eq!(recognizer.state, State::Idle);
+    }
+}
@@ -0,0 +1,637 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::{debug, warn};
+use winit::{
+    event::{ElementState, KeyEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use super::gamepad::{
+    GamepadAxis, GamepadButton, GamepadEvent, GamepadId, Gamepads, apply_deadzone,
+};
+use crate::util::components::scene::NodeId;
+
+/// Bindings used for actions the config file doesn't mention.
+const DEFAULT_BINDINGS: [(&str, Binding); 3] = [
+    ("toggle_fullscreen", Binding::Key(KeyCode::F11)),
+    ("toggle_exclusive_fullscreen", Binding::Key(KeyCode::F10)),
+    ("quit", Binding::Key(KeyCode::Escape)),
+];
+
+/// Stick and trigger values closer to rest than this read as 0.0.
+pub const DEFAULT_DEADZONE: f32 = 0.15;
+
+/// Something that triggers an action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    /// The button on any connected gamepad.
+    Gamepad(GamepadButton),
+}
+
+/// File the action bindings are read from, under the user config directory.
+///
+/// Each line binds an action to one or more keys named like winit's `KeyCode`, or gamepad
+/// buttons named like `GamepadButton` with a `Gamepad` prefix, e.g.
+///
+/// ```text
+/// # Comments start with a hash
+/// toggle_fullscreen = F11, KeyF, GamepadSelect
+/// ```
+pub fn bindings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("bindings.conf"))
+}
+
+/// Keys and gamepad buttons held down and the changes since the last `Input::end_frame`, by
+/// physical key so that bindings don't depend on the keyboard layout.
+#[derive(Default)]
+pub struct Input {
+    pressed: HashSet<KeyCode>,
+    just_pressed: HashSet<KeyCode>,
+    just_released: HashSet<KeyCode>,
+    bindings: HashMap<String, Vec<Binding>>,
+    gamepads: Gamepads,
+    gamepad_states: HashMap<GamepadId, GamepadState>,
+    just_connected: Vec<GamepadId>,
+    just_disconnected: Vec<GamepadId>,
+    deadzone: f32,
+    just_tapped: Vec<NodeId>,
+    just_long_pressed: Vec<NodeId>,
+}
+
+#[derive(Default)]
+struct GamepadState {
+    name: String,
+    pressed: HashSet<GamepadButton>,
+    just_pressed: HashSet<GamepadButton>,
+    just_released: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+}
+
+impl Input {
+    /// Uses the bindings from `bindings_path`, falling back to the defaults.
+    pub fn new() -> Self {
+        let overrides = bindings_path()
+            .filter(|path| path.exists())
+            .map(|path| load_bindings(&path))
+            .unwrap_or_default();
+        Self::with_bindings(overrides)
+    }
+    /// Uses `overrides` for the actions they bind and the defaults for the others.
+    pub fn with_bindings(overrides: HashMap<String, Vec<Binding>>) -> Self {
+        let mut bindings: HashMap<String, Vec<Binding>> = DEFAULT_BINDINGS
+            .iter()
+            .map(|(action, binding)| (action.to_string(), vec![*binding]))
+            .collect();
+        bindings.extend(overrides);
+        Input {
+            bindings,
+            deadzone: DEFAULT_DEADZONE,
+            ..Default::default()
+        }
+    }
+    pub fn handle_key_event(&mut self, event: &KeyEvent) {
+        if let PhysicalKey::Code(key) = event.physical_key {
+            self.handle_key(key, event.state);
+        }
+    }
+    pub fn handle_key(&mut self, key: KeyCode, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                // Repeats while the key is held are not new presses
+                if self.pressed.insert(key) {
+                    self.just_pressed.insert(key);
+                }
+            }
+            ElementState::Released => {
+                if self.pressed.remove(&key) {
+                    self.just_released.insert(key);
+                }
+            }
+        }
+    }
+    /// Reads the gamepads, returning whether anything changed.
+    pub fn poll_gamepads(&mut self) -> bool {
+        let events = self.gamepads.poll();
+        for event in &events {
+            self.handle_gamepad_event(event);
+        }
+        !events.is_empty()
+    }
+    fn handle_gamepad_event(&mut self, event: &GamepadEvent) {
+        match event {
+            GamepadEvent::Connected { id, name } => {
+                self.gamepad_states.insert(
+                    *id,
+                    GamepadState {
+                        name: name.clone(),
+                        ..Default::default()
+                    },
+                );
+                self.just_connected.push(*id);
+            }
+            GamepadEvent::Disconnected { id } => {
+                // The state is kept until `end_frame` so that held buttons are seen released
+                if let Some(state) = self.gamepad_states.get_mut(id) {
+                    let pressed: Vec<GamepadButton> = state.pressed.drain().collect();
+                    state.just_released.extend(pressed);
+                    state.axes.clear();
+                }
+                self.just_disconnected.push(*id);
+            }
+            GamepadEvent::Button {
+                id,
+                button,
+                pressed,
+            } => {
+                let Some(state) = self.gamepad_states.get_mut(id) else {
+                    return;
+                };
+                if *pressed && state.pressed.insert(*button) {
+                    state.just_pressed.insert(*button);
+                } else if !*pressed && state.pressed.remove(button) {
+                    state.just_released.insert(*button);
+                }
+            }
+            GamepadEvent::Axis { id, axis, value } => {
+                if let Some(state) = self.gamepad_states.get_mut(id) {
+                    state.axes.insert(*axis, *value);
+                }
+            }
+        }
+    }
+    /// How long until `poll_gamepads` should be called again, `None` without gamepad support.
+    pub fn gamepad_poll_interval(&self) -> Option<Duration> {
+        self.gamepads.poll_interval()
+    }
+    /// Forgets the presses, releases and connections of this frame, call once the app logic has
+    /// run.
+    pub fn end_frame(&mut self) {
+        for id in &self.just_disconnected {
+            self.gamepad_states.remove(id);
+        }
+        self.just_pressed.clear();
+        self.just_released.clear();
+        for state in self.gamepad_states.values_mut() {
+            state.just_pressed.clear();
+            state.just_released.clear();
+        }
+        self.just_connected.clear();
+        self.just_disconnected.clear();
+        self.just_tapped.clear();
+        self.just_long_pressed.clear();
+    }
+    /// Records the node picked by a tap.
+    pub fn handle_tap(&mut self, node: NodeId) {
+        self.just_tapped.push(node);
+    }
+    /// Records the node picked by a long press.
+    pub fn handle_long_press(&mut self, node: NodeId) {
+        self.just_long_pressed.push(node);
+    }
+    /// Releases every key, e.g. when the window loses focus and misses the release events.
+    pub fn release_all(&mut self) {
+        self.just_released.extend(self.pressed.drain());
+    }
+    pub fn is_pressed(&self, key: KeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+    pub fn is_just_pressed(&self, key: KeyCode) -> bool {
+        self.just_pressed.contains(&key)
+    }
+    pub fn is_just_released(&self, key: KeyCode) -> bool {
+        self.just_released.contains(&key)
+    }
+    /// Gamepads in no particular order.
+    pub fn gamepads(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.gamepad_states.keys().copied()
+    }
+    pub fn gamepad_name(&self, id: GamepadId) -> Option<&str> {
+        self.gamepad_states
+            .get(&id)
+            .map(|state| state.name.as_str())
+    }
+    pub fn just_connected(&self) -> &[GamepadId] {
+        &self.just_connected
+    }
+    pub fn just_disconnected(&self) -> &[GamepadId] {
+        &self.just_disconnected
+    }
+    /// Nodes picked by taps this frame, in the order they were tapped.
+    pub fn just_tapped(&self) -> &[NodeId] {
+        &self.just_tapped
+    }
+    /// Nodes picked by long presses this frame, in the order they were pressed.
+    pub fn just_long_pressed(&self) -> &[NodeId] {
+        &self.just_long_pressed
+    }
+    pub fn is_button_pressed(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad_states
+            .get(&id)
+            .is_some_and(|state| state.pressed.contains(&button))
+    }
+    pub fn is_button_just_pressed(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad_states
+            .get(&id)
+            .is_some_and(|state| state.just_pressed.contains(&button))
+    }
+    pub fn is_button_just_released(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad_states
+            .get(&id)
+            .is_some_and(|state| state.just_released.contains(&button))
+    }
+    /// Value of the axis with the deadzone applied, 0.0 for unknown gamepads and axes.
+    pub fn axis(&self, id: GamepadId, axis: GamepadAxis) -> f32 {
+        let value = self
+            .gamepad_states
+            .get(&id)
+            .and_then(|state| state.axes.get(&axis))
+            .copied()
+            .unwrap_or(0.0);
+        apply_deadzone(value, self.deadzone)
+    }
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.99);
+    }
+    /// Whether anything bound to `action` is held down.
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.action_bindings(action).any(|binding| match binding {
+            Binding::Key(key) => self.is_pressed(*key),
+            Binding::Gamepad(button) => self
+                .gamepads()
+                .any(|id| self.is_button_pressed(id, *button)),
+        })
+    }
+    /// Whether anything bound to `action` was pressed this frame.
+    pub fn action_just_pressed(&self, action: &str) -> bool {
+        self.action_bindings(action).any(|binding| match binding {
+            Binding::Key(key) => self.is_just_pressed(*key),
+            Binding::Gamepad(button) => self
+                .gamepads()
+                .any(|id| self.is_button_just_pressed(id, *button)),
+        })
+    }
+    /// Whether anything bound to `action` was released this frame.
+    pub fn action_just_released(&self, action: &str) -> bool {
+        self.action_bindings(action).any(|binding| match binding {
+            Binding::Key(key) => self.is_just_released(*key),
+            Binding::Gamepad(button) => self
+                .gamepads()
+                .any(|id| self.is_button_just_released(id, *button)),
+        })
+    }
+    /// Replaces what is bound to `action`.
+    pub fn bind(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.bindings.insert(action.to_owned(), bindings);
+    }
+    fn action_bindings(&self, action: &str) -> impl Iterator<Item = &Binding> {
+        self.bindings.get(action).into_iter().flatten()
+    }
+}
+
+/// Reads the bindings in `path`, skipping the lines that can't be parsed.
+pub fn load_bindings(path: &Path) -> HashMap<String, Vec<Binding>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            warn!(target: "input", "Could not read bindings from {}: {err}", path.display());
+            return HashMap::new();
+        }
+    };
+
+    let mut bindings = HashMap::new();
+    for (line_i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((action, keys)) = line.split_once('=') else {
+            warn!(target: "input", "{}:{}: expected `action = key`", path.display(), line_i + 1);
+            continue;
+        };
+        let keys: Result<Vec<Binding>, &str> = keys
+            .split(',')
+            .map(|key| parse_binding(key.trim()).ok_or(key.trim()))
+            .collect();
+        match keys {
+            Ok(keys) => {
+                bindings.insert(action.trim().to_owned(), keys);
+            }
+            Err(key) => {
+                warn!(target: "input", "{}:{}: unknown key {key:?}", path.display(), line_i + 1)
+            }
+        }
+    }
+    debug!(target: "input", "Loaded {} bindings from {}", bindings.len(), path.display());
+    bindings
+}
+
+/// Parses a key name for `parse_key` or a `GamepadButton` variant prefixed with `Gamepad`.
+pub fn parse_binding(name: &str) -> Option<Binding> {
+    if let Some(button) = name.strip_prefix("Gamepad") {
+        return GamepadButton::ALL
+            .into_iter()
+            .find(|variant| format!("{variant:?}") == button)
+            .map(Binding::Gamepad);
+    }
+    parse_key(name).map(Binding::Key)
+}
+
+/// Parses the name of a `KeyCode` variant, e.g. `KeyA`, `Digit1`, `F11` or `Escape`.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA,
+        KeyCode::KeyB,
+        KeyCode::KeyC,
+        KeyCode::KeyD,
+        KeyCode::KeyE,
+        KeyCode::KeyF,
+        KeyCode::KeyG,
+        KeyCode::KeyH,
+        KeyCode::KeyI,
+        KeyCode::KeyJ,
+        KeyCode::KeyK,
+        KeyCode::KeyL,
+        KeyCode::KeyM,
+        KeyCode::KeyN,
+        KeyCode::KeyO,
+        KeyCode::KeyP,
+        KeyCode::KeyQ,
+        KeyCode::KeyR,
+        KeyCode::KeyS,
+        KeyCode::KeyT,
+        KeyCode::KeyU,
+        KeyCode::KeyV,
+        KeyCode::KeyW,
+        KeyCode::KeyX,
+        KeyCode::KeyY,
+        KeyCode::KeyZ,
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0,
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    const FUNCTION_KEYS: [KeyCode; 12] = [
+        KeyCode::F1,
+        KeyCode::F2,
+        KeyCode::F3,
+        KeyCode::F4,
+        KeyCode::F5,
+        KeyCode::F6,
+        KeyCode::F7,
+        KeyCode::F8,
+        KeyCode::F9,
+        KeyCode::F10,
+        KeyCode::F11,
+        KeyCode::F12,
+    ];
+    const OTHER_KEYS: [KeyCode; 26] = [
+        KeyCode::Escape,
+        KeyCode::Space,
+        KeyCode::Enter,
+        KeyCode::Tab,
+        KeyCode::Backspace,
+        KeyCode::Delete,
+        KeyCode::Insert,
+        KeyCode::Home,
+        KeyCode::End,
+        KeyCode::PageUp,
+        KeyCode::PageDown,
+        KeyCode::ArrowUp,
+        KeyCode::ArrowDown,
+        KeyCode::ArrowLeft,
+        KeyCode::ArrowRight,
+        KeyCode::ShiftLeft,
+        KeyCode::ShiftRight,
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::AltLeft,
+        KeyCode::AltRight,
+        KeyCode::Minus,
+        KeyCode::Equal,
+        KeyCode::Comma,
+        KeyCode::Period,
+        KeyCode::Slash,
+    ];
+
+    // The variant names are what `KeyCode` prints with `Debug`
+    LETTERS
+        .into_iter()
+        .chain(DIGITS)
+        .chain(FUNCTION_KEYS)
+        .chain(OTHER_KEYS)
+        .find(|key| format!("{key:?}") == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAD: GamepadId = GamepadId(0);
+
+    fn button(button: GamepadButton, pressed: bool) -> GamepadEvent {
+        GamepadEvent::Button {
+            id: PAD,
+            button,
+            pressed,
+        }
+    }
+
+    fn connected_input() -> Input {
+        let mut input = Input {
+            deadzone: DEFAULT_DEADZONE,
+            ..Default::default()
+        };
+        input.handle_gamepad_event(&GamepadEvent::Connected {
+            id: PAD,
+            name: "test pad".to_owned(),
+        });
+        input
+    }
+
+    #[test]
+    fn key_repeats_are_not_presses() {
+        let mut input = Input::default();
+        input.handle_key(KeyCode::KeyA, ElementState::Pressed);
+        assert!(input.is_pressed(KeyCode::KeyA));
+        assert!(input.is_just_pressed(KeyCode::KeyA));
+        input.end_frame();
+
+        input.handle_key(KeyCode::KeyA, ElementState::Pressed);
+        assert!(input.is_pressed(KeyCode::KeyA));
+        assert!(!input.is_just_pressed(KeyCode::KeyA));
+
+        input.handle_key(KeyCode::KeyA, ElementState::Released);
+        assert!(!input.is_pressed(KeyCode::KeyA));
+        assert!(input.is_just_released(KeyCode::KeyA));
+        input.end_frame();
+        assert!(!input.is_just_released(KeyCode::KeyA));
+
+        // A release without a press, e.g. a key held before the window had focus
+        input.handle_key(KeyCode::KeyB, ElementState::Released);
+        assert!(!input.is_just_released(KeyCode::KeyB));
+    }
+
+    #[test]
+    fn release_all_releases_held_keys() {
+        let mut input = Input::default();
+        input.handle_key(KeyCode::KeyA, ElementState::Pressed);
+        input.handle_key(KeyCode::KeyB, ElementState::Pressed);
+        input.end_frame();
+        input.release_all();
+        for key in [KeyCode::KeyA, KeyCode::KeyB] {
+            assert!(!input.is_pressed(key));
+            assert!(input.is_just_released(key));
+        }
+        input.end_frame();
+        input.release_all();
+        assert!(!input.is_just_released(KeyCode::KeyA));
+    }
+
+    #[test]
+    fn overrides_replace_defaults() {
+        let mut input = Input::with_bindings(HashMap::from([(
+            "quit".to_owned(),
+            vec![Binding::Key(KeyCode::KeyQ)],
+        )]));
+        input.handle_key(KeyCode::Escape, ElementState::Pressed);
+        input.handle_key(KeyCode::F11, ElementState::Pressed);
+        assert!(!input.action_just_pressed("quit"));
+        assert!(input.action_just_pressed("toggle_fullscreen"));
+        input.handle_key(KeyCode::KeyQ, ElementState::Pressed);
+        assert!(input.action_just_pressed("quit"));
+        assert!(!input.action_just_pressed("unbound"));
+    }
+
+    #[test]
+    fn load_bindings_file() {
+        let path = std::env::temp_dir().join(format!("bindings-{}.conf", std::process::id()));
+        fs::write(
+            &path,
+            "# comment\n\
+             \n\
+             quit = KeyQ, GamepadStart\n\
+             no equals sign\n\
+             jump = Space, NotAKey\n\
+             \ttoggle_fullscreen=KeyF\n",
+        )
+        .unwrap();
+        let bindings = load_bindings(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(
+            bindings["quit"],
+            vec![
+                Binding::Key(KeyCode::KeyQ),
+                Binding::Gamepad(GamepadButton::Start)
+            ]
+        );
+        assert_eq!(
+            bindings["toggle_fullscreen"],
+            vec![Binding::Key(KeyCode::KeyF)]
+        );
+        // A line with an unknown key is skipped as a whole
+        assert!(!bindings.contains_key("jump"));
+        assert!(load_bindings(&path).is_empty());
+    }
+
+    #[test]
+    fn parse_bindings() {
+        assert_eq!(parse_binding("F11"), Some(Binding::Key(KeyCode::F11)));
+        assert_eq!(parse_binding("KeyA"), Some(Binding::Key(KeyCode::KeyA)));
+        assert_eq!(parse_binding("Digit1"), Some(Binding::Key(KeyCode::Digit1)));
+        assert_eq!(
+            parse_binding("GamepadSelect"),
+            Some(Binding::Gamepad(GamepadButton::Select))
+        );
+        assert_eq!(
+            parse_binding("GamepadDPadUp"),
+            Some(Binding::Gamepad(GamepadButton::DPadUp))
+        );
+        assert_eq!(parse_binding("GamepadBogus"), None);
+        assert_eq!(parse_binding("Gamepad"), None);
+        assert_eq!(parse_binding("NotAKey"), None);
+    }
+
+    #[test]
+    fn gamepad_button_edges() {
+        let mut input = connected_input();
+        assert_eq!(input.just_connected(), &[PAD]);
+        assert_eq!(input.gamepad_name(PAD), Some("test pad"));
+
+        input.handle_gamepad_event(&button(GamepadButton::South, true));
+        assert!(input.is_button_pressed(PAD, GamepadButton::South));
+        assert!(input.is_button_just_pressed(PAD, GamepadButton::South));
+        input.end_frame();
+        assert!(input.just_connected().is_empty());
+
+        // A second press event while held is not a new press
+        input.handle_gamepad_event(&button(GamepadButton::South, true));
+        assert!(input.is_button_pressed(PAD, GamepadButton::South));
+        assert!(!input.is_button_just_pressed(PAD, GamepadButton::South));
+
+        input.handle_gamepad_event(&button(GamepadButton::South, false));
+        assert!(!input.is_button_pressed(PAD, GamepadButton::South));
+        assert!(input.is_button_just_released(PAD, GamepadButton::South));
+        input.end_frame();
+        assert!(!input.is_button_just_released(PAD, GamepadButton::South));
+
+        // Releasing a button that wasn't held doesn't count as a release
+        input.handle_gamepad_event(&button(GamepadButton::East, false));
+        assert!(!input.is_button_just_released(PAD, GamepadButton::East));
+    }
+
+    #[test]
+    fn gamepad_actions_and_disconnect() {
+        let mut input = connected_input();
+        input.bind("jump", vec![Binding::Gamepad(GamepadButton::South)]);
+        input.handle_gamepad_event(&button(GamepadButton::South, true));
+        assert!(input.action_just_pressed("jump"));
+        assert!(input.action_pressed("jump"));
+
+        input.end_frame();
+        input.handle_gamepad_event(&GamepadEvent::Disconnected { id: PAD });
+        assert_eq!(input.just_disconnected(), &[PAD]);
+        assert!(!input.action_pressed("jump"));
+        // Buttons held while unplugged are released
+        assert!(input.action_just_released("jump"));
+        input.end_frame();
+        assert!(input.gamepads().next().is_none());
+        assert!(!input.action_just_released("jump"));
+        // Events of unknown gamepads are ignored
+        input.handle_gamepad_event(&button(GamepadButton::South, true));
+        assert!(!input.is_button_pressed(PAD, GamepadButton::South));
+    }
+
+    #[test]
+    fn axis_deadzone() {
+        let mut input = connected_input();
+        input.handle_gamepad_event(&GamepadEvent::Axis {
+            id: PAD,
+            axis: GamepadAxis::LeftX,
+            value: 0.1,
+        });
+        assert_eq!(input.axis(PAD, GamepadAxis::LeftX), 0.0);
+        input.handle_gamepad_event(&GamepadEvent::Axis {
+            id: PAD,
+            axis: GamepadAxis::LeftX,
+            value: -1.0,
+        });
+        assert_eq!(input.axis(PAD, GamepadAxis::LeftX), -1.0);
+        assert_eq!(input.axis(PAD, GamepadAxis::RightY), 0.0);
+    }
+}
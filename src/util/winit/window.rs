@@ -0,0 +1,95 @@
+use log::warn;
+use winit::{
+    dpi::LogicalSize,
+    monitor::MonitorHandle,
+    window::{Fullscreen, Icon, WindowAttributes},
+};
+
+/// How the window covers the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    /// A window without decorations covering the monitor, keeping the desktop video mode.
+    Borderless,
+    /// Takes over the monitor with its largest video mode, at its highest refresh rate.
+    Exclusive,
+}
+
+/// Options chosen when creating the window of an `App`.
+#[derive(Clone, Debug)]
+pub struct WindowSettings {
+    pub title: String,
+    /// Inner size in logical pixels, chosen by the platform when `None`.
+    pub size: Option<[u32; 2]>,
+    pub min_size: Option<[u32; 2]>,
+    pub resizable: bool,
+    pub decorations: bool,
+    pub icon: Option<Icon>,
+    /// Lets the desktop show through where the window is not opaque, together with a
+    /// translucent `VulkanSettings::clear_color`.
+    pub transparent: bool,
+    pub mode: WindowMode,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            title: env!("CARGO_PKG_NAME").to_owned(),
+            size: None,
+            min_size: None,
+            resizable: true,
+            decorations: true,
+            icon: None,
+            transparent: false,
+            mode: WindowMode::Windowed,
+        }
+    }
+}
+
+impl WindowSettings {
+    /// `monitor` is the one a fullscreen window is shown on.
+    pub fn attributes(&self, monitor: Option<MonitorHandle>) -> WindowAttributes {
+        let mut attributes = WindowAttributes::default()
+            .with_title(self.title.clone())
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_window_icon(self.icon.clone())
+            .with_transparent(self.transparent)
+            .with_fullscreen(fullscreen(self.mode, monitor));
+        if let Some([width, height]) = self.size {
+            attributes = attributes.with_inner_size(LogicalSize::new(width, height));
+        }
+        if let Some([width, height]) = self.min_size {
+            attributes = attributes.with_min_inner_size(LogicalSize::new(width, height));
+        }
+        attributes
+    }
+}
+
+/// What to pass to `Window::set_fullscreen` for `mode` on `monitor`. Exclusive fullscreen falls
+/// back to borderless when the monitor is unknown or has no video modes.
+pub fn fullscreen(mode: WindowMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match mode {
+        WindowMode::Windowed => None,
+        WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        WindowMode::Exclusive => {
+            let video_mode = monitor.as_ref().and_then(|monitor| {
+                monitor.video_modes().max_by_key(|video_mode| {
+                    let size = video_mode.size();
+                    (
+                        size.width * size.height,
+                        video_mode.refresh_rate_millihertz(),
+                    )
+                })
+            });
+            match video_mode {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                None => {
+                    warn!(target: "window", "No video mode for exclusive fullscreen, using borderless");
+                    Some(Fullscreen::Borderless(monitor))
+                }
+            }
+        }
+    }
+}